@@ -1,40 +1,14 @@
-macro_rules! input_axis {
-    ($name:ident, $byte:expr) => {
-        paste::item! {
-            pub fn [<axis_ $name>](&self) -> u8 {
-                self.buffer[$byte]
-            }
-
-            pub fn [<set_axis_ $name>](&mut self, value: u8) {
-                self.buffer[$byte] = value;
-            }
-        }
-    };
-}
-
-macro_rules! input_button {
-    ($name:ident, $byte:expr, $bit:expr) => {
-        paste::item! {
-            pub fn [<btn_ $name>](&self) -> bool {
-                (self.buffer[$byte] & $bit) != 0
-            }
-
-            pub fn [<set_btn_ $name>](&mut self, down: bool) {
-                if down {
-                    self.buffer[$byte] |= $bit;
-                } else {
-                    self.buffer[$byte] &= !$bit;
-                }
-            }
-        }
-    };
-}
+use super::{
+    Axis, Button, Controller, DPad, TouchPoint, DPAD_DOWN, DPAD_LEFT, DPAD_RIGHT, DPAD_UP,
+};
 
 const TRIANGLE: u8 = 1 << 7;
 const CIRCLE: u8 = 1 << 6;
 const CROSS: u8 = 1 << 5;
 const SQUARE: u8 = 1 << 4;
 
+const DPAD: u8 = 0xF;
+
 const L1: u8 = 1 << 0;
 const R1: u8 = 1 << 1;
 
@@ -49,25 +23,113 @@ const OPTIONS: u8 = 1 << 5;
 const PSBUTTON: u8 = 1 << 0;
 const TOUCHBUTTON: u8 = 1 << 2 - 1;
 
+pub const TOUCHPAD_WIDTH: u16 = 1920;
+pub const TOUCHPAD_HEIGHT: u16 = 942;
+
+const TOUCH_PACKETS: usize = 33;
+const TOUCH_PACKET_COUNTER: usize = 34;
+const TOUCH_POINTS: usize = 35;
+const TOUCH_INACTIVE: u8 = 1 << 7;
+
+pub const USB_REPORT_SIZE: usize = 64;
+pub const BLUETOOTH_REPORT_SIZE: usize = 78;
+
+const BLUETOOTH_REPORT_ID: u8 = 0x11;
+// bluetooth reports carry two additional header bytes in front of the usb layout
+const BLUETOOTH_OFFSET: usize = 2;
+// bluetooth input reports are checksummed together with the hid transaction header
+const BLUETOOTH_INPUT_SEED: u8 = 0xA1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Usb,
+    Bluetooth,
+}
+
+/// Computes the crc32 that is appended to bluetooth reports.
+/// The `seed` is the hid transaction header that precedes the report on the wire.
+pub fn bluetooth_crc32(seed: u8, data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in std::iter::once(&seed).chain(data.iter()) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
 pub struct DS4 {
+    // report in usb layout, bluetooth reports are converted on the fly
     buffer: Vec<u8>,
+    // original bluetooth report which also holds the bytes the usb layout has no room for
+    bluetooth: Option<Vec<u8>>,
+    // set by the first touch point written to this report, the packet counter advances once
+    touched: bool,
 }
 
 #[allow(unused)]
 impl DS4 {
     pub fn new(buffer: &[u8]) -> Result<Self, &'static str> {
-        // report _should_ be 64 bytes long
-        if buffer.len() == 64 {
+        if buffer.len() == USB_REPORT_SIZE {
             Ok(Self {
                 buffer: buffer.to_vec(),
+                bluetooth: None,
+                touched: false,
+            })
+        } else if buffer.len() == BLUETOOTH_REPORT_SIZE && buffer[0] == BLUETOOTH_REPORT_ID {
+            let crc_offset = BLUETOOTH_REPORT_SIZE - 4;
+            let crc = u32::from_le_bytes([
+                buffer[crc_offset],
+                buffer[crc_offset + 1],
+                buffer[crc_offset + 2],
+                buffer[crc_offset + 3],
+            ]);
+            if crc != bluetooth_crc32(BLUETOOTH_INPUT_SEED, &buffer[..crc_offset]) {
+                return Err("invalid bluetooth report checksum");
+            }
+
+            let mut usb = vec![0u8; USB_REPORT_SIZE];
+            usb[0] = 0x01;
+            usb[1..]
+                .copy_from_slice(&buffer[BLUETOOTH_OFFSET + 1..BLUETOOTH_OFFSET + USB_REPORT_SIZE]);
+            Ok(Self {
+                buffer: usb,
+                bluetooth: Some(buffer.to_vec()),
+                touched: false,
             })
         } else {
             Err("not a hid response")
         }
     }
 
+    pub fn format(&self) -> ReportFormat {
+        if self.bluetooth.is_some() {
+            ReportFormat::Bluetooth
+        } else {
+            ReportFormat::Usb
+        }
+    }
+
+    /// Serializes the report in the format it was received in.
+    /// Bluetooth reports get their checksum recomputed.
     pub fn to_raw(&self) -> Vec<u8> {
-        self.buffer.clone()
+        match &self.bluetooth {
+            Some(bluetooth) => {
+                let mut buffer = bluetooth.clone();
+                buffer[BLUETOOTH_OFFSET + 1..BLUETOOTH_OFFSET + USB_REPORT_SIZE]
+                    .copy_from_slice(&self.buffer[1..]);
+                let crc_offset = BLUETOOTH_REPORT_SIZE - 4;
+                let crc = bluetooth_crc32(BLUETOOTH_INPUT_SEED, &buffer[..crc_offset]);
+                buffer[crc_offset..].copy_from_slice(&crc.to_le_bytes());
+                buffer
+            }
+            None => self.buffer.clone(),
+        }
     }
 
     input_axis!(lx, 1);
@@ -81,6 +143,18 @@ impl DS4 {
     input_button!(square, 5, SQUARE);
 
     // dpad is in hat format, 0x8 is released state
+    pub fn dpad(&self) -> DPad {
+        DPad::from_hat(self.buffer[5] & DPAD)
+    }
+
+    pub fn set_dpad(&mut self, dpad: DPad) {
+        self.buffer[5] = (self.buffer[5] & !DPAD) | dpad.to_hat();
+    }
+
+    input_dpad!(up, DPAD_UP);
+    input_dpad!(right, DPAD_RIGHT);
+    input_dpad!(down, DPAD_DOWN);
+    input_dpad!(left, DPAD_LEFT);
 
     input_button!(l1, 6, L1);
     input_button!(r1, 6, R1);
@@ -98,43 +172,60 @@ impl DS4 {
     input_button!(ps, 7, PSBUTTON);
     input_button!(touch, 7, TOUCHBUTTON);
 
-    pub fn set_btn(&mut self, button: &str, down: bool) {
-        match button {
-            "triangle" => self.set_btn_triangle(down),
-            "circle" => self.set_btn_circle(down),
-            "cross" => self.set_btn_cross(down),
-            "square" => self.set_btn_square(down),
-
-            "l1" => self.set_btn_l1(down),
-            "r1" => self.set_btn_r1(down),
+    // angular velocity, roughly 16 units per degree per second
+    input_imu!(gyro_x, 13); // pitch
+    input_imu!(gyro_y, 15); // yaw
+    input_imu!(gyro_z, 17); // roll
 
-            "l2" => self.set_btn_l2(down),
-            "r2" => self.set_btn_r2(down),
+    // acceleration, roughly 8192 units per g
+    input_imu!(accel_x, 19);
+    input_imu!(accel_y, 21);
+    input_imu!(accel_z, 23);
 
-            "l3" => self.set_btn_l3(down),
-            "r3" => self.set_btn_r3(down),
+    /// Number of touch packets contained in the report.
+    pub fn touch_packets(&self) -> u8 {
+        self.buffer[TOUCH_PACKETS]
+    }
 
-            "share" => self.set_btn_share(down),
-            "options" => self.set_btn_options(down),
-            "ps" => self.set_btn_ps(down),
-            "touch" => self.set_btn_touch(down),
+    pub fn set_touch_packets(&mut self, count: u8) {
+        self.buffer[TOUCH_PACKETS] = count;
+    }
 
-            _ => (),
-        }
+    pub fn touch_packet_counter(&self) -> u8 {
+        self.buffer[TOUCH_PACKET_COUNTER]
     }
 
-    pub fn set_axis(&mut self, axis: &str, value: u8) {
-        match axis {
-            "lx" => self.set_axis_lx(value),
-            "ly" => self.set_axis_ly(value),
-            "rx" => self.set_axis_rx(value),
-            "ry" => self.set_axis_ry(value),
+    pub fn set_touch_packet_counter(&mut self, counter: u8) {
+        self.buffer[TOUCH_PACKET_COUNTER] = counter;
+    }
 
-            "l2" => self.set_axis_l2(value),
-            "r2" => self.set_axis_r2(value),
+    /// Returns the finger (0 or 1) of the most recent touch packet,
+    /// other fingers are never touching.
+    pub fn touch_point(&self, finger: usize) -> TouchPoint {
+        if finger > 1 {
+            return TouchPoint::default();
+        }
+        let point = &self.buffer[TOUCH_POINTS + finger * 4..TOUCH_POINTS + finger * 4 + 4];
+        TouchPoint {
+            id: point[0] & !TOUCH_INACTIVE,
+            active: (point[0] & TOUCH_INACTIVE) == 0,
+            x: point[1] as u16 | ((point[2] as u16 & 0xF) << 8),
+            y: (point[2] as u16 >> 4) | ((point[3] as u16) << 4),
+        }
+    }
 
-            _ => (),
+    /// Overwrites the finger (0 or 1) of the most recent touch packet, other fingers are ignored.
+    pub fn set_touch_point(&mut self, finger: usize, point: TouchPoint) {
+        if finger > 1 {
+            return;
         }
+        let x = point.x.min(TOUCHPAD_WIDTH - 1);
+        let y = point.y.min(TOUCHPAD_HEIGHT - 1);
+        let buffer = &mut self.buffer[TOUCH_POINTS + finger * 4..TOUCH_POINTS + finger * 4 + 4];
+        buffer[0] = (point.id & !TOUCH_INACTIVE) | if point.active { 0 } else { TOUCH_INACTIVE };
+        buffer[1] = x as u8;
+        buffer[2] = ((x >> 8) as u8 & 0xF) | ((y as u8 & 0xF) << 4);
+        buffer[3] = (y >> 4) as u8;
     }
 
     pub fn frame_count(&self) -> u8 {
@@ -149,3 +240,251 @@ impl DS4 {
         (self.buffer[30] & 0x10) != 0
     }
 }
+
+impl Controller for DS4 {
+    fn btn(&self, button: Button) -> bool {
+        match button {
+            Button::Triangle => self.btn_triangle(),
+            Button::Circle => self.btn_circle(),
+            Button::Cross => self.btn_cross(),
+            Button::Square => self.btn_square(),
+
+            Button::DpadUp => self.btn_dpad_up(),
+            Button::DpadDown => self.btn_dpad_down(),
+            Button::DpadLeft => self.btn_dpad_left(),
+            Button::DpadRight => self.btn_dpad_right(),
+
+            Button::L1 => self.btn_l1(),
+            Button::R1 => self.btn_r1(),
+
+            Button::L2 => self.btn_l2(),
+            Button::R2 => self.btn_r2(),
+
+            Button::L3 => self.btn_l3(),
+            Button::R3 => self.btn_r3(),
+
+            Button::Share => self.btn_share(),
+            Button::Options => self.btn_options(),
+            Button::Ps => self.btn_ps(),
+            Button::Touch => self.btn_touch(),
+        }
+    }
+
+    fn set_btn(&mut self, button: Button, down: bool) {
+        match button {
+            Button::Triangle => self.set_btn_triangle(down),
+            Button::Circle => self.set_btn_circle(down),
+            Button::Cross => self.set_btn_cross(down),
+            Button::Square => self.set_btn_square(down),
+
+            Button::DpadUp => self.set_btn_dpad_up(down),
+            Button::DpadDown => self.set_btn_dpad_down(down),
+            Button::DpadLeft => self.set_btn_dpad_left(down),
+            Button::DpadRight => self.set_btn_dpad_right(down),
+
+            Button::L1 => self.set_btn_l1(down),
+            Button::R1 => self.set_btn_r1(down),
+
+            Button::L2 => self.set_btn_l2(down),
+            Button::R2 => self.set_btn_r2(down),
+
+            Button::L3 => self.set_btn_l3(down),
+            Button::R3 => self.set_btn_r3(down),
+
+            Button::Share => self.set_btn_share(down),
+            Button::Options => self.set_btn_options(down),
+            Button::Ps => self.set_btn_ps(down),
+            Button::Touch => self.set_btn_touch(down),
+        }
+    }
+
+    fn axis(&self, axis: Axis) -> u8 {
+        match axis {
+            Axis::Lx => self.axis_lx(),
+            Axis::Ly => self.axis_ly(),
+            Axis::Rx => self.axis_rx(),
+            Axis::Ry => self.axis_ry(),
+
+            Axis::L2 => self.axis_l2(),
+            Axis::R2 => self.axis_r2(),
+        }
+    }
+
+    fn set_axis(&mut self, axis: Axis, value: u8) {
+        match axis {
+            Axis::Lx => self.set_axis_lx(value),
+            Axis::Ly => self.set_axis_ly(value),
+            Axis::Rx => self.set_axis_rx(value),
+            Axis::Ry => self.set_axis_ry(value),
+
+            Axis::L2 => self.set_axis_l2(value),
+            Axis::R2 => self.set_axis_r2(value),
+        }
+    }
+
+    fn dpad(&self) -> DPad {
+        DS4::dpad(self)
+    }
+
+    fn set_dpad(&mut self, dpad: DPad) {
+        DS4::set_dpad(self, dpad)
+    }
+
+    fn touchpad_size(&self) -> [u16; 2] {
+        [TOUCHPAD_WIDTH, TOUCHPAD_HEIGHT]
+    }
+
+    fn touch_point(&self, finger: usize) -> TouchPoint {
+        DS4::touch_point(self, finger)
+    }
+
+    fn set_touch_point(&mut self, finger: usize, point: TouchPoint) {
+        if !self.touched {
+            self.touched = true;
+            self.set_touch_packets(self.touch_packets().max(1));
+            self.set_touch_packet_counter(self.touch_packet_counter().wrapping_add(1));
+        }
+        DS4::set_touch_point(self, finger, point);
+    }
+
+    fn gyro(&self) -> [i16; 3] {
+        [self.gyro_x(), self.gyro_y(), self.gyro_z()]
+    }
+
+    fn set_gyro(&mut self, gyro: [i16; 3]) {
+        self.set_gyro_x(gyro[0]);
+        self.set_gyro_y(gyro[1]);
+        self.set_gyro_z(gyro[2]);
+    }
+
+    fn accel(&self) -> [i16; 3] {
+        [self.accel_x(), self.accel_y(), self.accel_z()]
+    }
+
+    fn set_accel(&mut self, accel: [i16; 3]) {
+        self.set_accel_x(accel[0]);
+        self.set_accel_y(accel[1]);
+        self.set_accel_z(accel[2]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hand-built from the report layout, not captured from a pad: idle controller over
+    // bluetooth with sticks near center, dpad released, frame 5, a bit of gyro noise,
+    // gravity on the accelerometer and 80% battery.
+    // the checksum was computed independently with zlib's crc32 over 0xA1 + report.
+    // TODO: replace it with a 0x11 report captured from a paired DS4 for the parsing and
+    // round trip tests, the corruption test only needs some report with a valid checksum.
+    const BLUETOOTH_REPORT: [u8; BLUETOOTH_REPORT_SIZE] = [
+        0x11, 0xc0, 0x00, 0x7f, 0x81, 0x80, 0x7e, 0x08, 0x00, 0x14, 0x00, 0x00, //
+        0x34, 0x12, 0x15, 0x02, 0x00, 0xfd, 0xff, 0x01, 0x00, 0x78, 0x00, 0xa4, //
+        0x1f, 0xd4, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, //
+        0x00, 0x80, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x00, 0x00, 0x54, 0x84, 0x58, 0x7f,
+    ];
+
+    #[test]
+    fn crc32_check_value() {
+        // standard crc32 check value of "123456789"
+        assert_eq!(bluetooth_crc32(b'1', b"23456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn parse_bluetooth_report() {
+        let ds4 = DS4::new(&BLUETOOTH_REPORT).unwrap();
+        assert_eq!(ds4.format(), ReportFormat::Bluetooth);
+        assert_eq!(
+            [ds4.axis_lx(), ds4.axis_ly(), ds4.axis_rx(), ds4.axis_ry()],
+            [0x7f, 0x81, 0x80, 0x7e]
+        );
+        assert_eq!(ds4.dpad(), DPad::Released);
+        assert!(!ds4.btn_cross());
+        assert_eq!(ds4.frame_count(), 5);
+        assert_eq!([ds4.gyro_x(), ds4.gyro_y(), ds4.gyro_z()], [2, -3, 1]);
+        assert_eq!(
+            [ds4.accel_x(), ds4.accel_y(), ds4.accel_z()],
+            [120, 8100, -300]
+        );
+        assert_eq!(ds4.battery(), 80);
+    }
+
+    #[test]
+    fn unmodified_bluetooth_report_round_trips() {
+        let ds4 = DS4::new(&BLUETOOTH_REPORT).unwrap();
+        assert_eq!(ds4.to_raw(), BLUETOOTH_REPORT.to_vec());
+    }
+
+    #[test]
+    fn modified_bluetooth_report_gets_new_checksum() {
+        let mut ds4 = DS4::new(&BLUETOOTH_REPORT).unwrap();
+        ds4.set_btn_cross(true);
+        ds4.set_axis_lx(0);
+
+        let raw = ds4.to_raw();
+        assert_eq!(raw.len(), BLUETOOTH_REPORT_SIZE);
+        assert_eq!(raw[3], 0x00);
+        assert_eq!(raw[7], 0x28);
+        assert_eq!(raw[74..], 0xDF7E_9324u32.to_le_bytes());
+
+        // the consumer side has to accept the report again
+        assert!(DS4::new(&raw).unwrap().btn_cross());
+    }
+
+    #[test]
+    fn corrupted_bluetooth_report_is_rejected() {
+        let mut report = BLUETOOTH_REPORT;
+        report[10] ^= 0x01;
+        assert!(DS4::new(&report).is_err());
+    }
+
+    #[test]
+    fn usb_report_is_passed_through() {
+        let mut report = [0u8; USB_REPORT_SIZE];
+        report[0] = 0x01;
+        report[1..].copy_from_slice(&BLUETOOTH_REPORT[3..66]);
+
+        let mut ds4 = DS4::new(&report).unwrap();
+        assert_eq!(ds4.format(), ReportFormat::Usb);
+        assert_eq!(ds4.to_raw(), report.to_vec());
+
+        ds4.set_btn_cross(true);
+        assert_eq!(ds4.to_raw()[5], 0x28);
+    }
+
+    #[test]
+    fn touch_points() {
+        let mut report = [0u8; USB_REPORT_SIZE];
+        report[0] = 0x01;
+        report[TOUCH_POINTS] = TOUCH_INACTIVE;
+        report[TOUCH_POINTS + 4] = TOUCH_INACTIVE;
+        let mut ds4 = DS4::new(&report).unwrap();
+
+        let point = TouchPoint {
+            id: 3,
+            active: true,
+            x: 0x123,
+            y: 0x345,
+        };
+        Controller::set_touch_point(&mut ds4, 0, point);
+        Controller::set_touch_point(&mut ds4, 1, TouchPoint { id: 4, ..point });
+        assert_eq!(ds4.touch_point(0), point);
+        assert_eq!(ds4.touch_point(1).id, 4);
+        assert_eq!(
+            ds4.to_raw()[TOUCH_POINTS..TOUCH_POINTS + 4],
+            [0x03, 0x23, 0x51, 0x34]
+        );
+        // both fingers are in the same packet
+        assert_eq!(ds4.touch_packets(), 1);
+        assert_eq!(ds4.touch_packet_counter(), 1);
+
+        // the touchpad only tracks two fingers
+        Controller::set_touch_point(&mut ds4, 2, point);
+        assert!(!ds4.touch_point(2).active);
+        assert_eq!(ds4.to_raw()[TOUCH_POINTS + 8..], report[TOUCH_POINTS + 8..]);
+    }
+}
@@ -0,0 +1,339 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+// feature report ids in the usb layout
+pub const CALIBRATION: u8 = 0x02;
+pub const PAIRING: u8 = 0x12;
+pub const MAC_ADDRESS: u8 = 0x81;
+pub const FIRMWARE: u8 = 0xA3;
+
+const CALIBRATION_SIZE: usize = 37;
+const PAIRING_SIZE: usize = 16;
+const MAC_ADDRESS_SIZE: usize = 7;
+const FIRMWARE_SIZE: usize = 49;
+
+// the same three bytes show up in every pairing report that has been captured
+const PAIRING_UNKNOWN: [u8; 3] = [0x08, 0x25, 0x00];
+
+// build date and time are null padded ascii strings
+const FIRMWARE_STRING_SIZE: usize = 16;
+
+/// A bluetooth device address, written as `"a4:15:66:01:02:03"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl MacAddress {
+    /// The controller sends addresses least significant byte first.
+    fn to_reversed_bytes(self) -> [u8; 6] {
+        let mut bytes = self.0;
+        bytes.reverse();
+        bytes
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split([':', '-']).collect::<Vec<_>>();
+        if parts.len() != 6 {
+            return Err("invalid mac address");
+        }
+
+        let mut address = [0u8; 6];
+        for (byte, part) in address.iter_mut().zip(parts) {
+            if part.len() != 2 {
+                return Err("invalid mac address");
+            }
+            *byte = u8::from_str_radix(part, 16).map_err(|_| "invalid mac address")?;
+        }
+        Ok(Self(address))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let a = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            a[0], a[1], a[2], a[3], a[4], a[5]
+        )
+    }
+}
+
+impl Serialize for MacAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MacAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let address = String::deserialize(deserializer)?;
+        address
+            .parse()
+            .map_err(|err| de::Error::custom(format!("{} `{}`", err, address)))
+    }
+}
+
+/// Imu calibration as reported in feature report 0x02.
+/// Every triple is ordered pitch, yaw, roll for the gyro and x, y, z for the accelerometer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Calibration {
+    pub gyro_bias: [i16; 3],
+    pub gyro_plus: [i16; 3],
+    pub gyro_minus: [i16; 3],
+    pub gyro_speed_plus: i16,
+    pub gyro_speed_minus: i16,
+    pub accel_plus: [i16; 3],
+    pub accel_minus: [i16; 3],
+}
+
+impl Default for Calibration {
+    // an ideal controller, ±2000 deg/s on the gyro and ±1g at 8192 units on the accelerometer
+    fn default() -> Self {
+        Self {
+            gyro_bias: [0; 3],
+            gyro_plus: [8832; 3],
+            gyro_minus: [-8832; 3],
+            gyro_speed_plus: 540,
+            gyro_speed_minus: 540,
+            accel_plus: [8192; 3],
+            accel_minus: [-8192; 3],
+        }
+    }
+}
+
+/// Everything the virtual controller tells about itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceIdentity {
+    pub mac_address: MacAddress,
+    /// Address of the console or pc the controller is paired with.
+    pub host_address: MacAddress,
+    pub firmware_date: String,
+    pub firmware_time: String,
+    pub hardware_version: u16,
+    pub software_version: u32,
+    pub firmware_version: u16,
+    pub calibration: Calibration,
+}
+
+impl Default for DeviceIdentity {
+    fn default() -> Self {
+        Self {
+            mac_address: MacAddress([0xA4, 0x15, 0x66, 0x6D, 0x70, 0x6C]),
+            host_address: MacAddress([0x00; 6]),
+            firmware_date: "Sep 21 2018".to_string(),
+            firmware_time: "04:50:51".to_string(),
+            hardware_version: 0xB400,
+            software_version: 0x0100_0001,
+            firmware_version: 0xA01B,
+            calibration: Calibration::default(),
+        }
+    }
+}
+
+impl DeviceIdentity {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !self.firmware_date.is_ascii() || self.firmware_date.len() >= FIRMWARE_STRING_SIZE {
+            return Err("firmware date has to be ascii and shorter than 16 characters");
+        }
+        if !self.firmware_time.is_ascii() || self.firmware_time.len() >= FIRMWARE_STRING_SIZE {
+            return Err("firmware time has to be ascii and shorter than 16 characters");
+        }
+        Ok(())
+    }
+}
+
+/// Builds the response to a feature report request,
+/// `None` if a real controller does not know the report either.
+pub fn feature_report(identity: &DeviceIdentity, report_id: u8) -> Option<Vec<u8>> {
+    match report_id {
+        CALIBRATION => Some(calibration_report(&identity.calibration)),
+        PAIRING => Some(pairing_report(identity)),
+        MAC_ADDRESS => Some(mac_address_report(identity)),
+        FIRMWARE => Some(firmware_report(identity)),
+        _ => None,
+    }
+}
+
+pub fn calibration_report(calibration: &Calibration) -> Vec<u8> {
+    // over usb plus and minus alternate per axis, bluetooth groups them instead
+    let values = [
+        calibration.gyro_bias[0],
+        calibration.gyro_bias[1],
+        calibration.gyro_bias[2],
+        calibration.gyro_plus[0],
+        calibration.gyro_minus[0],
+        calibration.gyro_plus[1],
+        calibration.gyro_minus[1],
+        calibration.gyro_plus[2],
+        calibration.gyro_minus[2],
+        calibration.gyro_speed_plus,
+        calibration.gyro_speed_minus,
+        calibration.accel_plus[0],
+        calibration.accel_minus[0],
+        calibration.accel_plus[1],
+        calibration.accel_minus[1],
+        calibration.accel_plus[2],
+        calibration.accel_minus[2],
+    ];
+
+    let mut report = vec![0u8; CALIBRATION_SIZE];
+    report[0] = CALIBRATION;
+    for (i, value) in values.iter().enumerate() {
+        report[1 + i * 2..3 + i * 2].copy_from_slice(&value.to_le_bytes());
+    }
+    report
+}
+
+pub fn pairing_report(identity: &DeviceIdentity) -> Vec<u8> {
+    let mut report = vec![0u8; PAIRING_SIZE];
+    report[0] = PAIRING;
+    report[1..7].copy_from_slice(&identity.mac_address.to_reversed_bytes());
+    report[7..10].copy_from_slice(&PAIRING_UNKNOWN);
+    report[10..16].copy_from_slice(&identity.host_address.to_reversed_bytes());
+    report
+}
+
+pub fn mac_address_report(identity: &DeviceIdentity) -> Vec<u8> {
+    let mut report = vec![0u8; MAC_ADDRESS_SIZE];
+    report[0] = MAC_ADDRESS;
+    report[1..7].copy_from_slice(&identity.mac_address.to_reversed_bytes());
+    report
+}
+
+pub fn firmware_report(identity: &DeviceIdentity) -> Vec<u8> {
+    let mut report = vec![0u8; FIRMWARE_SIZE];
+    report[0] = FIRMWARE;
+
+    let date = identity.firmware_date.as_bytes();
+    let date = &date[..date.len().min(FIRMWARE_STRING_SIZE - 1)];
+    report[1..1 + date.len()].copy_from_slice(date);
+    let time = identity.firmware_time.as_bytes();
+    let time = &time[..time.len().min(FIRMWARE_STRING_SIZE - 1)];
+    report[17..17 + time.len()].copy_from_slice(time);
+
+    report[35..37].copy_from_slice(&identity.hardware_version.to_le_bytes());
+    report[37..41].copy_from_slice(&identity.software_version.to_le_bytes());
+    report[41..43].copy_from_slice(&identity.firmware_version.to_le_bytes());
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> DeviceIdentity {
+        DeviceIdentity {
+            mac_address: "a4:15:66:01:02:03".parse().unwrap(),
+            host_address: "00:1a:7d:da:71:10".parse().unwrap(),
+            ..DeviceIdentity::default()
+        }
+    }
+
+    #[test]
+    fn calibration() {
+        #[rustfmt::skip]
+        let expected: [u8; 37] = [
+            0x02,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x80, 0x22, 0x80, 0xDD, 0x80, 0x22, 0x80, 0xDD, 0x80, 0x22, 0x80, 0xDD,
+            0x1C, 0x02, 0x1C, 0x02,
+            0x00, 0x20, 0x00, 0xE0, 0x00, 0x20, 0x00, 0xE0, 0x00, 0x20, 0x00, 0xE0,
+            0x00, 0x00,
+        ];
+        assert_eq!(
+            feature_report(&identity(), CALIBRATION).unwrap(),
+            expected.to_vec()
+        );
+    }
+
+    #[test]
+    fn calibration_order() {
+        let calibration = Calibration {
+            gyro_bias: [1, 2, 3],
+            gyro_plus: [4, 6, 8],
+            gyro_minus: [5, 7, 9],
+            gyro_speed_plus: 10,
+            gyro_speed_minus: 11,
+            accel_plus: [12, 14, 16],
+            accel_minus: [13, 15, 17],
+        };
+        let report = calibration_report(&calibration);
+        for i in 0..17 {
+            assert_eq!(report[1 + i * 2], i as u8 + 1);
+            assert_eq!(report[2 + i * 2], 0);
+        }
+    }
+
+    #[test]
+    fn pairing() {
+        let expected: [u8; 16] = [
+            0x12, 0x03, 0x02, 0x01, 0x66, 0x15, 0xA4, 0x08, 0x25, 0x00, 0x10, 0x71, 0xDA, 0x7D,
+            0x1A, 0x00,
+        ];
+        assert_eq!(
+            feature_report(&identity(), PAIRING).unwrap(),
+            expected.to_vec()
+        );
+    }
+
+    #[test]
+    fn mac_address() {
+        let expected: [u8; 7] = [0x81, 0x03, 0x02, 0x01, 0x66, 0x15, 0xA4];
+        assert_eq!(
+            feature_report(&identity(), MAC_ADDRESS).unwrap(),
+            expected.to_vec()
+        );
+    }
+
+    #[test]
+    fn firmware() {
+        #[rustfmt::skip]
+        let expected: [u8; 49] = [
+            0xA3,
+            b'S', b'e', b'p', b' ', b'2', b'1', b' ', b'2', b'0', b'1', b'8', 0, 0, 0, 0, 0,
+            b'0', b'4', b':', b'5', b'0', b':', b'5', b'1', 0, 0, 0, 0, 0, 0, 0, 0,
+            0x00, 0x00,
+            0x00, 0xB4,
+            0x01, 0x00, 0x00, 0x01,
+            0x1B, 0xA0,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            feature_report(&identity(), FIRMWARE).unwrap(),
+            expected.to_vec()
+        );
+    }
+
+    #[test]
+    fn unknown_report() {
+        assert_eq!(feature_report(&identity(), 0x05), None);
+    }
+
+    #[test]
+    fn mac_address_format() {
+        let address = "A4-15-66-01-02-03".parse::<MacAddress>().unwrap();
+        assert_eq!(address.to_string(), "a4:15:66:01:02:03");
+        assert!("a4:15:66:01:02".parse::<MacAddress>().is_err());
+        assert!("a4:15:66:01:02:zz".parse::<MacAddress>().is_err());
+        assert!("a4:15:66:01:02:003".parse::<MacAddress>().is_err());
+    }
+
+    #[test]
+    fn identity_from_json() {
+        let identity: DeviceIdentity =
+            serde_json::from_str(r#"{ "mac_address": "a4:15:66:01:02:03" }"#).unwrap();
+        assert_eq!(
+            identity.mac_address,
+            MacAddress([0xA4, 0x15, 0x66, 1, 2, 3])
+        );
+        assert_eq!(identity.calibration, Calibration::default());
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+use super::ds4::ReportFormat;
+
+pub const USB_OUTPUT_REPORT_SIZE: usize = 32;
+pub const BLUETOOTH_OUTPUT_REPORT_SIZE: usize = 78;
+
+const USB_REPORT_ID: u8 = 0x05;
+const BLUETOOTH_REPORT_ID: u8 = 0x11;
+
+// bluetooth output reports carry two additional header bytes in front of the usb layout
+const BLUETOOTH_OFFSET: usize = 2;
+
+// the flags tell which parts of the report the client wants to change
+const FLAGS: usize = 1;
+const FLAG_RUMBLE: u8 = 1 << 0;
+const FLAG_LIGHTBAR: u8 = 1 << 1;
+const FLAG_FLASH: u8 = 1 << 2;
+
+const RUMBLE_SMALL: usize = 4;
+const RUMBLE_LARGE: usize = 5;
+const LIGHTBAR: usize = 6;
+const FLASH_ON: usize = 9;
+const FLASH_OFF: usize = 10;
+
+// flash durations count in steps of 10ms, 255 is 2.55s
+const FLASH_UNIT_MS: u64 = 10;
+
+lazy_static! {
+    // thread safe storage for the feedback the client most recently sent to the controller
+    pub static ref OUTPUT_STATE: RwLock<OutputState> = RwLock::new(OutputState::default());
+}
+
+/// DualShock 4 output report as written by the client (usb report id 0x05, bluetooth 0x11).
+pub struct DS4OutputReport {
+    buffer: Vec<u8>,
+    format: ReportFormat,
+}
+
+#[allow(unused)]
+impl DS4OutputReport {
+    pub fn new(buffer: &[u8]) -> Result<Self, &'static str> {
+        if buffer.len() == USB_OUTPUT_REPORT_SIZE && buffer[0] == USB_REPORT_ID {
+            Ok(Self {
+                buffer: buffer.to_vec(),
+                format: ReportFormat::Usb,
+            })
+        } else if buffer.len() == BLUETOOTH_OUTPUT_REPORT_SIZE && buffer[0] == BLUETOOTH_REPORT_ID {
+            // strip the bluetooth header so the usb offsets apply
+            Ok(Self {
+                buffer: buffer[BLUETOOTH_OFFSET..].to_vec(),
+                format: ReportFormat::Bluetooth,
+            })
+        } else {
+            Err("not an output report")
+        }
+    }
+
+    pub fn format(&self) -> ReportFormat {
+        self.format
+    }
+
+    pub fn has_rumble(&self) -> bool {
+        (self.buffer[FLAGS] & FLAG_RUMBLE) != 0
+    }
+
+    pub fn has_lightbar(&self) -> bool {
+        (self.buffer[FLAGS] & FLAG_LIGHTBAR) != 0
+    }
+
+    pub fn has_flash(&self) -> bool {
+        (self.buffer[FLAGS] & FLAG_FLASH) != 0
+    }
+
+    /// Strength of the small, high frequency motor on the right.
+    pub fn rumble_small(&self) -> u8 {
+        self.buffer[RUMBLE_SMALL]
+    }
+
+    /// Strength of the large, low frequency motor on the left.
+    pub fn rumble_large(&self) -> u8 {
+        self.buffer[RUMBLE_LARGE]
+    }
+
+    /// Lightbar colour as red, green, blue.
+    pub fn lightbar(&self) -> [u8; 3] {
+        [
+            self.buffer[LIGHTBAR],
+            self.buffer[LIGHTBAR + 1],
+            self.buffer[LIGHTBAR + 2],
+        ]
+    }
+
+    /// How long the lightbar stays lit while flashing.
+    pub fn flash_on(&self) -> Duration {
+        Duration::from_millis(self.buffer[FLASH_ON] as u64 * FLASH_UNIT_MS)
+    }
+
+    /// How long the lightbar stays dark while flashing.
+    pub fn flash_off(&self) -> Duration {
+        Duration::from_millis(self.buffer[FLASH_OFF] as u64 * FLASH_UNIT_MS)
+    }
+}
+
+/// The feedback a controller is currently showing, made up from all output reports so far.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OutputState {
+    pub rumble_small: u8,
+    pub rumble_large: u8,
+    pub lightbar: [u8; 3],
+    /// Both zero while the lightbar is not flashing.
+    pub flash_on: Duration,
+    pub flash_off: Duration,
+    /// When the client last wrote an output report.
+    pub updated_at: Option<Instant>,
+}
+
+impl OutputState {
+    /// Takes over the parts of the report its flags mark as valid.
+    pub fn apply(&mut self, report: &DS4OutputReport, now: Instant) {
+        if report.has_rumble() {
+            self.rumble_small = report.rumble_small();
+            self.rumble_large = report.rumble_large();
+        }
+        if report.has_lightbar() {
+            self.lightbar = report.lightbar();
+        }
+        if report.has_flash() {
+            self.flash_on = report.flash_on();
+            self.flash_off = report.flash_off();
+        }
+        self.updated_at = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usb_report(flags: u8) -> Vec<u8> {
+        let mut buffer = vec![0u8; USB_OUTPUT_REPORT_SIZE];
+        buffer[0] = USB_REPORT_ID;
+        buffer[FLAGS] = flags;
+        buffer[RUMBLE_SMALL] = 0x40;
+        buffer[RUMBLE_LARGE] = 0xC0;
+        buffer[LIGHTBAR..LIGHTBAR + 3].copy_from_slice(&[0xFF, 0x20, 0x00]);
+        buffer[FLASH_ON] = 25;
+        buffer[FLASH_OFF] = 50;
+        buffer
+    }
+
+    #[test]
+    fn usb_output_report() {
+        let report = DS4OutputReport::new(&usb_report(0x07)).unwrap();
+        assert_eq!(report.format(), ReportFormat::Usb);
+        assert_eq!(report.rumble_small(), 0x40);
+        assert_eq!(report.rumble_large(), 0xC0);
+        assert_eq!(report.lightbar(), [0xFF, 0x20, 0x00]);
+        assert_eq!(report.flash_on(), Duration::from_millis(250));
+        assert_eq!(report.flash_off(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn bluetooth_output_report() {
+        let mut buffer = vec![0u8; BLUETOOTH_OUTPUT_REPORT_SIZE];
+        buffer[0] = BLUETOOTH_REPORT_ID;
+        buffer[BLUETOOTH_OFFSET..BLUETOOTH_OFFSET + USB_OUTPUT_REPORT_SIZE]
+            .copy_from_slice(&usb_report(0x07));
+
+        let report = DS4OutputReport::new(&buffer).unwrap();
+        assert_eq!(report.format(), ReportFormat::Bluetooth);
+        assert_eq!(report.rumble_large(), 0xC0);
+        assert_eq!(report.lightbar(), [0xFF, 0x20, 0x00]);
+    }
+
+    #[test]
+    fn state_follows_flags() {
+        let now = Instant::now();
+        let mut state = OutputState::default();
+        state.apply(
+            &DS4OutputReport::new(&usb_report(FLAG_LIGHTBAR)).unwrap(),
+            now,
+        );
+        assert_eq!(state.lightbar, [0xFF, 0x20, 0x00]);
+        assert_eq!(state.rumble_large, 0);
+        assert_eq!(state.flash_on, Duration::from_millis(0));
+        assert_eq!(state.updated_at, Some(now));
+
+        state.apply(
+            &DS4OutputReport::new(&usb_report(FLAG_RUMBLE)).unwrap(),
+            now,
+        );
+        assert_eq!(state.rumble_small, 0x40);
+        assert_eq!(state.rumble_large, 0xC0);
+    }
+
+    #[test]
+    fn input_reports_are_rejected() {
+        assert!(DS4OutputReport::new(&[0x01; 64]).is_err());
+        assert!(DS4OutputReport::new(&[0x05; 31]).is_err());
+    }
+}
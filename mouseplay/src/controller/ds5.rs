@@ -0,0 +1,550 @@
+use super::{
+    Axis, Button, Controller, DPad, TouchPoint, DPAD_DOWN, DPAD_LEFT, DPAD_RIGHT, DPAD_UP,
+};
+
+const TRIANGLE: u8 = 1 << 7;
+const CIRCLE: u8 = 1 << 6;
+const CROSS: u8 = 1 << 5;
+const SQUARE: u8 = 1 << 4;
+
+const DPAD: u8 = 0xF;
+
+const L1: u8 = 1 << 0;
+const R1: u8 = 1 << 1;
+
+const L2: u8 = 1 << 2;
+const R2: u8 = 1 << 3;
+
+const CREATE: u8 = 1 << 4;
+const OPTIONS: u8 = 1 << 5;
+
+const L3: u8 = 1 << 6;
+const R3: u8 = 1 << 7;
+
+const PSBUTTON: u8 = 1 << 0;
+const TOUCHBUTTON: u8 = 1 << 1;
+const MUTE: u8 = 1 << 2;
+
+pub const TOUCHPAD_WIDTH: u16 = 1920;
+pub const TOUCHPAD_HEIGHT: u16 = 1080;
+
+const TOUCH_POINTS: usize = 33;
+const TOUCH_TIMESTAMP: usize = 41;
+const TOUCH_INACTIVE: u8 = 1 << 7;
+
+const TRIGGER_R2_STATUS: usize = 42;
+const TRIGGER_L2_STATUS: usize = 43;
+
+const BATTERY: usize = 53;
+
+/// DualSense usb input report (report id 0x01).
+pub struct DS5 {
+    buffer: Vec<u8>,
+    // set by the first touch point written to this report, the timestamp advances once
+    touched: bool,
+}
+
+#[allow(unused)]
+impl DS5 {
+    pub fn new(buffer: &[u8]) -> Result<Self, &'static str> {
+        // report _should_ be 64 bytes long and start with the report id
+        if buffer.len() == 64 && buffer[0] == 0x01 {
+            Ok(Self {
+                buffer: buffer.to_vec(),
+                touched: false,
+            })
+        } else {
+            Err("not a hid response")
+        }
+    }
+
+    pub fn to_raw(&self) -> Vec<u8> {
+        self.buffer.clone()
+    }
+
+    input_axis!(lx, 1);
+    input_axis!(ly, 2);
+    input_axis!(rx, 3);
+    input_axis!(ry, 4);
+
+    input_axis!(l2, 5);
+    input_axis!(r2, 6);
+
+    input_button!(triangle, 8, TRIANGLE);
+    input_button!(circle, 8, CIRCLE);
+    input_button!(cross, 8, CROSS);
+    input_button!(square, 8, SQUARE);
+
+    // dpad is in hat format, 0x8 is released state
+    pub fn dpad(&self) -> DPad {
+        DPad::from_hat(self.buffer[8] & DPAD)
+    }
+
+    pub fn set_dpad(&mut self, dpad: DPad) {
+        self.buffer[8] = (self.buffer[8] & !DPAD) | dpad.to_hat();
+    }
+
+    input_dpad!(up, DPAD_UP);
+    input_dpad!(right, DPAD_RIGHT);
+    input_dpad!(down, DPAD_DOWN);
+    input_dpad!(left, DPAD_LEFT);
+
+    input_button!(l1, 9, L1);
+    input_button!(r1, 9, R1);
+
+    input_button!(l2, 9, L2);
+    input_button!(r2, 9, R2);
+
+    input_button!(l3, 9, L3);
+    input_button!(r3, 9, R3);
+
+    // the create button takes the place of share on the ds4
+    input_button!(share, 9, CREATE);
+    input_button!(options, 9, OPTIONS);
+    input_button!(ps, 10, PSBUTTON);
+    input_button!(touch, 10, TOUCHBUTTON);
+    input_button!(mute, 10, MUTE);
+
+    // angular velocity
+    input_imu!(gyro_x, 16); // pitch
+    input_imu!(gyro_y, 18); // yaw
+    input_imu!(gyro_z, 20); // roll
+
+    // acceleration
+    input_imu!(accel_x, 22);
+    input_imu!(accel_y, 24);
+    input_imu!(accel_z, 26);
+
+    pub fn sensor_timestamp(&self) -> u32 {
+        u32::from_le_bytes([
+            self.buffer[28],
+            self.buffer[29],
+            self.buffer[30],
+            self.buffer[31],
+        ])
+    }
+
+    pub fn touch_timestamp(&self) -> u8 {
+        self.buffer[TOUCH_TIMESTAMP]
+    }
+
+    pub fn set_touch_timestamp(&mut self, timestamp: u8) {
+        self.buffer[TOUCH_TIMESTAMP] = timestamp;
+    }
+
+    /// Returns one of the two fingers (0 or 1) on the touchpad, other fingers are never touching.
+    pub fn touch_point(&self, finger: usize) -> TouchPoint {
+        if finger > 1 {
+            return TouchPoint::default();
+        }
+        let point = &self.buffer[TOUCH_POINTS + finger * 4..TOUCH_POINTS + finger * 4 + 4];
+        TouchPoint {
+            id: point[0] & !TOUCH_INACTIVE,
+            active: (point[0] & TOUCH_INACTIVE) == 0,
+            x: point[1] as u16 | ((point[2] as u16 & 0xF) << 8),
+            y: (point[2] as u16 >> 4) | ((point[3] as u16) << 4),
+        }
+    }
+
+    /// Overwrites one of the two fingers (0 or 1) on the touchpad, other fingers are ignored.
+    pub fn set_touch_point(&mut self, finger: usize, point: TouchPoint) {
+        if finger > 1 {
+            return;
+        }
+        let x = point.x.min(TOUCHPAD_WIDTH - 1);
+        let y = point.y.min(TOUCHPAD_HEIGHT - 1);
+        let buffer = &mut self.buffer[TOUCH_POINTS + finger * 4..TOUCH_POINTS + finger * 4 + 4];
+        buffer[0] = (point.id & !TOUCH_INACTIVE) | if point.active { 0 } else { TOUCH_INACTIVE };
+        buffer[1] = x as u8;
+        buffer[2] = ((x >> 8) as u8 & 0xF) | ((y as u8 & 0xF) << 4);
+        buffer[3] = (y >> 4) as u8;
+    }
+
+    /// Adaptive trigger state, the low nibble holds the stop location
+    /// and the high nibble the status of the active effect.
+    pub fn trigger_status_l2(&self) -> u8 {
+        self.buffer[TRIGGER_L2_STATUS]
+    }
+
+    pub fn trigger_status_r2(&self) -> u8 {
+        self.buffer[TRIGGER_R2_STATUS]
+    }
+
+    pub fn frame_count(&self) -> u8 {
+        self.buffer[7]
+    }
+
+    pub fn battery(&self) -> u8 {
+        ((self.buffer[BATTERY] & 0xF) * 10).min(100)
+    }
+
+    pub fn is_charging(&self) -> bool {
+        (self.buffer[BATTERY] >> 4) == 0x1
+    }
+}
+
+impl Controller for DS5 {
+    fn btn(&self, button: Button) -> bool {
+        match button {
+            Button::Triangle => self.btn_triangle(),
+            Button::Circle => self.btn_circle(),
+            Button::Cross => self.btn_cross(),
+            Button::Square => self.btn_square(),
+
+            Button::DpadUp => self.btn_dpad_up(),
+            Button::DpadDown => self.btn_dpad_down(),
+            Button::DpadLeft => self.btn_dpad_left(),
+            Button::DpadRight => self.btn_dpad_right(),
+
+            Button::L1 => self.btn_l1(),
+            Button::R1 => self.btn_r1(),
+
+            Button::L2 => self.btn_l2(),
+            Button::R2 => self.btn_r2(),
+
+            Button::L3 => self.btn_l3(),
+            Button::R3 => self.btn_r3(),
+
+            Button::Share => self.btn_share(),
+            Button::Options => self.btn_options(),
+            Button::Ps => self.btn_ps(),
+            Button::Touch => self.btn_touch(),
+        }
+    }
+
+    fn set_btn(&mut self, button: Button, down: bool) {
+        match button {
+            Button::Triangle => self.set_btn_triangle(down),
+            Button::Circle => self.set_btn_circle(down),
+            Button::Cross => self.set_btn_cross(down),
+            Button::Square => self.set_btn_square(down),
+
+            Button::DpadUp => self.set_btn_dpad_up(down),
+            Button::DpadDown => self.set_btn_dpad_down(down),
+            Button::DpadLeft => self.set_btn_dpad_left(down),
+            Button::DpadRight => self.set_btn_dpad_right(down),
+
+            Button::L1 => self.set_btn_l1(down),
+            Button::R1 => self.set_btn_r1(down),
+
+            Button::L2 => self.set_btn_l2(down),
+            Button::R2 => self.set_btn_r2(down),
+
+            Button::L3 => self.set_btn_l3(down),
+            Button::R3 => self.set_btn_r3(down),
+
+            Button::Share => self.set_btn_share(down),
+            Button::Options => self.set_btn_options(down),
+            Button::Ps => self.set_btn_ps(down),
+            Button::Touch => self.set_btn_touch(down),
+        }
+    }
+
+    fn axis(&self, axis: Axis) -> u8 {
+        match axis {
+            Axis::Lx => self.axis_lx(),
+            Axis::Ly => self.axis_ly(),
+            Axis::Rx => self.axis_rx(),
+            Axis::Ry => self.axis_ry(),
+
+            Axis::L2 => self.axis_l2(),
+            Axis::R2 => self.axis_r2(),
+        }
+    }
+
+    fn set_axis(&mut self, axis: Axis, value: u8) {
+        match axis {
+            Axis::Lx => self.set_axis_lx(value),
+            Axis::Ly => self.set_axis_ly(value),
+            Axis::Rx => self.set_axis_rx(value),
+            Axis::Ry => self.set_axis_ry(value),
+
+            Axis::L2 => self.set_axis_l2(value),
+            Axis::R2 => self.set_axis_r2(value),
+        }
+    }
+
+    fn dpad(&self) -> DPad {
+        DS5::dpad(self)
+    }
+
+    fn set_dpad(&mut self, dpad: DPad) {
+        DS5::set_dpad(self, dpad)
+    }
+
+    fn touchpad_size(&self) -> [u16; 2] {
+        [TOUCHPAD_WIDTH, TOUCHPAD_HEIGHT]
+    }
+
+    fn touch_point(&self, finger: usize) -> TouchPoint {
+        DS5::touch_point(self, finger)
+    }
+
+    fn set_touch_point(&mut self, finger: usize, point: TouchPoint) {
+        if !self.touched {
+            self.touched = true;
+            self.set_touch_timestamp(self.touch_timestamp().wrapping_add(1));
+        }
+        DS5::set_touch_point(self, finger, point);
+    }
+
+    fn gyro(&self) -> [i16; 3] {
+        [self.gyro_x(), self.gyro_y(), self.gyro_z()]
+    }
+
+    fn set_gyro(&mut self, gyro: [i16; 3]) {
+        self.set_gyro_x(gyro[0]);
+        self.set_gyro_y(gyro[1]);
+        self.set_gyro_z(gyro[2]);
+    }
+
+    fn accel(&self) -> [i16; 3] {
+        [self.accel_x(), self.accel_y(), self.accel_z()]
+    }
+
+    fn set_accel(&mut self, accel: [i16; 3]) {
+        self.set_accel_x(accel[0]);
+        self.set_accel_y(accel[1]);
+        self.set_accel_z(accel[2]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const REPORT: [u8; 64] = [
+        0x01,
+        0x7F, 0x81, 0x80, 0x7E, // sticks
+        0x10, 0xF0, // triggers
+        0x2A, // frame count
+        0x18, 0x81, 0x04, // square and released dpad, l1 and r3, mute
+        0x00, 0x00, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0xFD, 0xFF, 0x01, 0x00, // gyro
+        0x78, 0x00, 0xA4, 0x1F, 0xD4, 0xFE, // accel
+        0x78, 0x56, 0x34, 0x12, // sensor timestamp
+        0x00,
+        0x05, 0x23, 0x51, 0x34, // finger 0
+        0x86, 0x00, 0x00, 0x00, // finger 1, not touching
+        0x09, // touch timestamp
+        0x21, 0x03, // r2 and l2 trigger status
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x18, // battery
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    // the report with every button released and the dpad centered
+    fn neutral() -> DS5 {
+        let mut report = REPORT;
+        report[8] = 0x08;
+        report[9] = 0x00;
+        report[10] = 0x00;
+        DS5::new(&report).unwrap()
+    }
+
+    // the bytes of `ds5` that differ from the neutral report
+    fn changes(ds5: &DS5) -> Vec<(usize, u8)> {
+        let neutral = neutral().to_raw();
+        ds5.to_raw()
+            .iter()
+            .enumerate()
+            .filter(|(i, byte)| neutral[*i] != **byte)
+            .map(|(i, byte)| (i, *byte))
+            .collect()
+    }
+
+    #[test]
+    fn parse_usb_report() {
+        let ds5 = DS5::new(&REPORT).unwrap();
+        assert_eq!(
+            [ds5.axis_lx(), ds5.axis_ly(), ds5.axis_rx(), ds5.axis_ry()],
+            [0x7F, 0x81, 0x80, 0x7E]
+        );
+        assert_eq!([ds5.axis_l2(), ds5.axis_r2()], [0x10, 0xF0]);
+        assert_eq!(ds5.frame_count(), 0x2A);
+        assert!(ds5.btn_square() && ds5.btn_l1() && ds5.btn_r3() && ds5.btn_mute());
+        assert!(!ds5.btn_triangle() && !ds5.btn_r1() && !ds5.btn_ps() && !ds5.btn_touch());
+        assert_eq!(ds5.dpad(), DPad::Released);
+        assert_eq!([ds5.gyro_x(), ds5.gyro_y(), ds5.gyro_z()], [2, -3, 1]);
+        assert_eq!(
+            [ds5.accel_x(), ds5.accel_y(), ds5.accel_z()],
+            [120, 8100, -300]
+        );
+        assert_eq!(ds5.sensor_timestamp(), 0x1234_5678);
+        assert_eq!(
+            ds5.touch_point(0),
+            TouchPoint {
+                id: 5,
+                active: true,
+                x: 0x123,
+                y: 0x345
+            }
+        );
+        assert_eq!(
+            ds5.touch_point(1),
+            TouchPoint {
+                id: 6,
+                active: false,
+                x: 0,
+                y: 0
+            }
+        );
+        assert_eq!(ds5.touch_timestamp(), 0x09);
+        assert_eq!(
+            [ds5.trigger_status_r2(), ds5.trigger_status_l2()],
+            [0x21, 0x03]
+        );
+        assert_eq!(ds5.battery(), 80);
+        assert!(ds5.is_charging());
+    }
+
+    #[test]
+    fn unmodified_report_round_trips() {
+        assert_eq!(DS5::new(&REPORT).unwrap().to_raw(), REPORT.to_vec());
+    }
+
+    #[test]
+    fn other_reports_are_rejected() {
+        assert!(DS5::new(&REPORT[..63]).is_err());
+        let mut report = REPORT;
+        report[0] = 0x31;
+        assert!(DS5::new(&report).is_err());
+    }
+
+    #[test]
+    fn buttons() {
+        let buttons = [
+            (Button::Triangle, 8, 0x88),
+            (Button::Circle, 8, 0x48),
+            (Button::Cross, 8, 0x28),
+            (Button::Square, 8, 0x18),
+            (Button::L1, 9, 0x01),
+            (Button::R1, 9, 0x02),
+            (Button::L2, 9, 0x04),
+            (Button::R2, 9, 0x08),
+            (Button::Share, 9, 0x10),
+            (Button::Options, 9, 0x20),
+            (Button::L3, 9, 0x40),
+            (Button::R3, 9, 0x80),
+            (Button::Ps, 10, 0x01),
+            (Button::Touch, 10, 0x02),
+        ];
+        for (button, byte, value) in buttons.iter() {
+            let mut ds5 = neutral();
+            ds5.set_btn(*button, true);
+            assert!(ds5.btn(*button), "{:?}", button);
+            assert_eq!(changes(&ds5), vec![(*byte, *value)], "{:?}", button);
+            ds5.set_btn(*button, false);
+            assert!(changes(&ds5).is_empty(), "{:?}", button);
+        }
+
+        // mute has no ds4 counterpart and is only reachable on the report itself
+        let mut ds5 = neutral();
+        ds5.set_btn_mute(true);
+        assert_eq!(changes(&ds5), vec![(10, 0x04)]);
+    }
+
+    #[test]
+    fn dpad() {
+        let mut ds5 = neutral();
+        ds5.set_btn(Button::Triangle, true);
+        ds5.set_btn(Button::DpadUp, true);
+        ds5.set_btn(Button::DpadRight, true);
+        assert_eq!(ds5.dpad(), DPad::UpRight);
+        assert_eq!(changes(&ds5), vec![(8, 0x81)]);
+
+        ds5.set_btn(Button::DpadUp, false);
+        assert!(!ds5.btn(Button::DpadUp) && ds5.btn(Button::DpadRight));
+        assert_eq!(changes(&ds5), vec![(8, 0x82)]);
+
+        Controller::set_dpad(&mut ds5, DPad::DownLeft);
+        assert_eq!(changes(&ds5), vec![(8, 0x85)]);
+        Controller::set_dpad(&mut ds5, DPad::Released);
+        assert_eq!(changes(&ds5), vec![(8, 0x88)]);
+    }
+
+    #[test]
+    fn axes() {
+        let mut ds5 = neutral();
+        ds5.set_axis(Axis::Lx, 0x00);
+        ds5.set_axis(Axis::Ly, 0x01);
+        ds5.set_axis(Axis::Rx, 0x02);
+        ds5.set_axis(Axis::Ry, 0x03);
+        ds5.set_axis(Axis::L2, 0x04);
+        ds5.set_axis(Axis::R2, 0x05);
+        assert_eq!(
+            changes(&ds5),
+            vec![
+                (1, 0x00),
+                (2, 0x01),
+                (3, 0x02),
+                (4, 0x03),
+                (5, 0x04),
+                (6, 0x05)
+            ]
+        );
+        assert_eq!(ds5.axis(Axis::R2), 0x05);
+    }
+
+    #[test]
+    fn imu() {
+        let mut ds5 = neutral();
+        ds5.set_gyro([0x0102, -2, 0x7FFF]);
+        ds5.set_accel([-0x8000, 0x0304, 0]);
+        assert_eq!(ds5.gyro(), [0x0102, -2, 0x7FFF]);
+        assert_eq!(ds5.accel(), [-0x8000, 0x0304, 0]);
+        assert_eq!(
+            ds5.to_raw()[16..28],
+            [0x02, 0x01, 0xFE, 0xFF, 0xFF, 0x7F, 0x00, 0x80, 0x04, 0x03, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn touch_points() {
+        let mut ds5 = neutral();
+        let point = TouchPoint {
+            id: 7,
+            active: true,
+            x: 0x456,
+            y: 0x123,
+        };
+        Controller::set_touch_point(&mut ds5, 0, point);
+        Controller::set_touch_point(&mut ds5, 1, TouchPoint { id: 8, ..point });
+        assert_eq!(ds5.touch_point(0), point);
+        assert_eq!(
+            changes(&ds5),
+            vec![
+                (33, 0x07),
+                (34, 0x56),
+                (35, 0x34),
+                (36, 0x12),
+                (37, 0x08),
+                (38, 0x56),
+                (39, 0x34),
+                (40, 0x12),
+                // stamped once for both fingers
+                (41, 0x0A),
+            ]
+        );
+
+        // positions are kept on the touchpad and only two fingers are tracked
+        Controller::set_touch_point(
+            &mut ds5,
+            0,
+            TouchPoint {
+                x: 5000,
+                y: 5000,
+                ..point
+            },
+        );
+        assert_eq!(
+            (ds5.touch_point(0).x, ds5.touch_point(0).y),
+            (TOUCHPAD_WIDTH - 1, TOUCHPAD_HEIGHT - 1)
+        );
+        let raw = ds5.to_raw();
+        Controller::set_touch_point(&mut ds5, 2, point);
+        assert!(!ds5.touch_point(2).active);
+        assert_eq!(ds5.to_raw(), raw);
+    }
+}
@@ -0,0 +1,64 @@
+macro_rules! input_axis {
+    ($name:ident, $byte:expr) => {
+        paste::item! {
+            pub fn [<axis_ $name>](&self) -> u8 {
+                self.buffer[$byte]
+            }
+
+            pub fn [<set_axis_ $name>](&mut self, value: u8) {
+                self.buffer[$byte] = value;
+            }
+        }
+    };
+}
+
+macro_rules! input_imu {
+    ($name:ident, $byte:expr) => {
+        paste::item! {
+            pub fn $name(&self) -> i16 {
+                i16::from_le_bytes([self.buffer[$byte], self.buffer[$byte + 1]])
+            }
+
+            pub fn [<set_ $name>](&mut self, value: i16) {
+                self.buffer[$byte..$byte + 2].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+    };
+}
+
+macro_rules! input_button {
+    ($name:ident, $byte:expr, $bit:expr) => {
+        paste::item! {
+            pub fn [<btn_ $name>](&self) -> bool {
+                (self.buffer[$byte] & $bit) != 0
+            }
+
+            pub fn [<set_btn_ $name>](&mut self, down: bool) {
+                if down {
+                    self.buffer[$byte] |= $bit;
+                } else {
+                    self.buffer[$byte] &= !$bit;
+                }
+            }
+        }
+    };
+}
+
+macro_rules! input_dpad {
+    ($name:ident, $direction:expr) => {
+        paste::item! {
+            pub fn [<btn_dpad_ $name>](&self) -> bool {
+                (self.dpad().directions() & $direction) != 0
+            }
+
+            pub fn [<set_btn_dpad_ $name>](&mut self, down: bool) {
+                let directions = self.dpad().directions();
+                if down {
+                    self.set_dpad(DPad::from_directions(directions | $direction));
+                } else {
+                    self.set_dpad(DPad::from_directions(directions & !$direction));
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+
+use super::{Axis, Button, Controller, DPad, TouchPoint};
+
+/// In-memory controller for testing mappings without a report buffer.
+/// Starts out in the same neutral state a physical controller reports when idle.
+pub struct MockController {
+    buttons: HashSet<Button>,
+    axes: [u8; 6],
+    dpad: DPad,
+    touch_points: [TouchPoint; 2],
+    gyro: [i16; 3],
+    accel: [i16; 3],
+}
+
+impl Default for MockController {
+    fn default() -> Self {
+        Self {
+            buttons: HashSet::new(),
+            axes: [128, 128, 128, 128, 0, 0],
+            dpad: DPad::Released,
+            touch_points: [TouchPoint::default(); 2],
+            gyro: [0; 3],
+            accel: [0; 3],
+        }
+    }
+}
+
+impl MockController {
+    fn axis_index(axis: Axis) -> usize {
+        match axis {
+            Axis::Lx => 0,
+            Axis::Ly => 1,
+            Axis::Rx => 2,
+            Axis::Ry => 3,
+            Axis::L2 => 4,
+            Axis::R2 => 5,
+        }
+    }
+
+    fn dpad_direction(button: Button) -> Option<u8> {
+        match button {
+            Button::DpadUp => Some(super::DPAD_UP),
+            Button::DpadDown => Some(super::DPAD_DOWN),
+            Button::DpadLeft => Some(super::DPAD_LEFT),
+            Button::DpadRight => Some(super::DPAD_RIGHT),
+            _ => None,
+        }
+    }
+}
+
+impl Controller for MockController {
+    fn btn(&self, button: Button) -> bool {
+        match Self::dpad_direction(button) {
+            Some(direction) => (self.dpad.directions() & direction) != 0,
+            None => self.buttons.contains(&button),
+        }
+    }
+
+    fn set_btn(&mut self, button: Button, down: bool) {
+        match Self::dpad_direction(button) {
+            Some(direction) => {
+                let directions = self.dpad.directions();
+                if down {
+                    self.dpad = DPad::from_directions(directions | direction);
+                } else {
+                    self.dpad = DPad::from_directions(directions & !direction);
+                }
+            }
+            None => {
+                if down {
+                    self.buttons.insert(button);
+                } else {
+                    self.buttons.remove(&button);
+                }
+            }
+        }
+    }
+
+    fn axis(&self, axis: Axis) -> u8 {
+        self.axes[Self::axis_index(axis)]
+    }
+
+    fn set_axis(&mut self, axis: Axis, value: u8) {
+        self.axes[Self::axis_index(axis)] = value;
+    }
+
+    fn dpad(&self) -> DPad {
+        self.dpad
+    }
+
+    fn set_dpad(&mut self, dpad: DPad) {
+        self.dpad = dpad;
+    }
+
+    fn touchpad_size(&self) -> [u16; 2] {
+        [super::ds4::TOUCHPAD_WIDTH, super::ds4::TOUCHPAD_HEIGHT]
+    }
+
+    fn touch_point(&self, finger: usize) -> TouchPoint {
+        self.touch_points[finger]
+    }
+
+    fn set_touch_point(&mut self, finger: usize, point: TouchPoint) {
+        self.touch_points[finger] = point;
+    }
+
+    fn gyro(&self) -> [i16; 3] {
+        self.gyro
+    }
+
+    fn set_gyro(&mut self, gyro: [i16; 3]) {
+        self.gyro = gyro;
+    }
+
+    fn accel(&self) -> [i16; 3] {
+        self.accel
+    }
+
+    fn set_accel(&mut self, accel: [i16; 3]) {
+        self.accel = accel;
+    }
+}
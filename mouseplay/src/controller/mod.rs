@@ -1 +1,236 @@
-pub mod ds4;
+#[macro_use]
+mod macros;
+
+pub mod ds4;
+pub mod ds4_feature;
+pub mod ds4_output;
+pub mod ds5;
+#[cfg(test)]
+pub mod mock;
+pub mod virtual_ds4;
+
+use serde::{Deserialize, Serialize};
+
+/// A digital output on the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Button {
+    Triangle,
+    Circle,
+    Cross,
+    Square,
+
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight,
+
+    L1,
+    R1,
+    L2,
+    R2,
+    L3,
+    R3,
+
+    Share,
+    Options,
+    Ps,
+    Touch,
+}
+
+/// An analog output on the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Axis {
+    Lx,
+    Ly,
+    Rx,
+    Ry,
+
+    L2,
+    R2,
+}
+
+impl Axis {
+    /// True for the stick axes, false for the triggers.
+    pub fn is_stick(self) -> bool {
+        !matches!(self, Axis::L2 | Axis::R2)
+    }
+
+    /// Value of the axis while it is left alone, sticks rest in the center and triggers at zero.
+    pub fn rest(self) -> u8 {
+        if self.is_stick() {
+            0x80
+        } else {
+            0
+        }
+    }
+}
+
+pub const DPAD_UP: u8 = 1 << 0;
+pub const DPAD_RIGHT: u8 = 1 << 1;
+pub const DPAD_DOWN: u8 = 1 << 2;
+pub const DPAD_LEFT: u8 = 1 << 3;
+
+/// State of the dpad as it is encoded in the hat switch nibble of the report.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DPad {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+    Released,
+}
+
+impl DPad {
+    pub fn from_hat(hat: u8) -> Self {
+        match hat {
+            0 => DPad::Up,
+            1 => DPad::UpRight,
+            2 => DPad::Right,
+            3 => DPad::DownRight,
+            4 => DPad::Down,
+            5 => DPad::DownLeft,
+            6 => DPad::Left,
+            7 => DPad::UpLeft,
+            _ => DPad::Released,
+        }
+    }
+
+    pub fn to_hat(self) -> u8 {
+        match self {
+            DPad::Up => 0,
+            DPad::UpRight => 1,
+            DPad::Right => 2,
+            DPad::DownRight => 3,
+            DPad::Down => 4,
+            DPad::DownLeft => 5,
+            DPad::Left => 6,
+            DPad::UpLeft => 7,
+            DPad::Released => 8,
+        }
+    }
+
+    /// Combines a set of held directions into a single hat state.
+    /// Opposing directions cancel each other out.
+    pub fn from_directions(directions: u8) -> Self {
+        let vertical = match (directions & DPAD_UP != 0, directions & DPAD_DOWN != 0) {
+            (true, false) => DPAD_UP,
+            (false, true) => DPAD_DOWN,
+            _ => 0,
+        };
+        let horizontal = match (directions & DPAD_LEFT != 0, directions & DPAD_RIGHT != 0) {
+            (true, false) => DPAD_LEFT,
+            (false, true) => DPAD_RIGHT,
+            _ => 0,
+        };
+        match vertical | horizontal {
+            DPAD_UP => DPad::Up,
+            d if d == DPAD_UP | DPAD_RIGHT => DPad::UpRight,
+            DPAD_RIGHT => DPad::Right,
+            d if d == DPAD_DOWN | DPAD_RIGHT => DPad::DownRight,
+            DPAD_DOWN => DPad::Down,
+            d if d == DPAD_DOWN | DPAD_LEFT => DPad::DownLeft,
+            DPAD_LEFT => DPad::Left,
+            d if d == DPAD_UP | DPAD_LEFT => DPad::UpLeft,
+            _ => DPad::Released,
+        }
+    }
+
+    /// Splits the hat state into the individual directions that are held.
+    pub fn directions(self) -> u8 {
+        match self {
+            DPad::Up => DPAD_UP,
+            DPad::UpRight => DPAD_UP | DPAD_RIGHT,
+            DPad::Right => DPAD_RIGHT,
+            DPad::DownRight => DPAD_DOWN | DPAD_RIGHT,
+            DPad::Down => DPAD_DOWN,
+            DPad::DownLeft => DPAD_DOWN | DPAD_LEFT,
+            DPad::Left => DPAD_LEFT,
+            DPad::UpLeft => DPAD_UP | DPAD_LEFT,
+            DPad::Released => 0,
+        }
+    }
+}
+
+/// A single finger on the touchpad.
+/// Coordinates are 12 bit values in the range of `TOUCHPAD_WIDTH` x `TOUCHPAD_HEIGHT`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TouchPoint {
+    pub id: u8,
+    pub active: bool,
+    pub x: u16,
+    pub y: u16,
+}
+
+/// The kind of controller behind a device handle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub enum ControllerKind {
+    DualShock4,
+    DualSense,
+}
+
+impl ControllerKind {
+    /// Determines the controller kind from the vendor and product id in a hid device path.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn from_device_path(path: &str) -> Option<Self> {
+        let path = path.to_lowercase();
+        if !path.contains("vid_054c") {
+            return None;
+        }
+
+        if path.contains("pid_05c4") || path.contains("pid_09cc") || path.contains("pid_0ba0") {
+            Some(ControllerKind::DualShock4)
+        } else if path.contains("pid_0ce6") || path.contains("pid_0df2") {
+            Some(ControllerKind::DualSense)
+        } else {
+            None
+        }
+    }
+}
+
+/// Common interface of all supported controller reports.
+/// Mappings are written against this trait so they work with every report format.
+pub trait Controller {
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn btn(&self, button: Button) -> bool;
+
+    fn set_btn(&mut self, button: Button, down: bool);
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn axis(&self, axis: Axis) -> u8;
+
+    fn set_axis(&mut self, axis: Axis, value: u8);
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn dpad(&self) -> DPad;
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn set_dpad(&mut self, dpad: DPad);
+
+    /// Size of the touchpad in touch point coordinates.
+    fn touchpad_size(&self) -> [u16; 2];
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn touch_point(&self, finger: usize) -> TouchPoint;
+
+    fn set_touch_point(&mut self, finger: usize, point: TouchPoint);
+
+    /// Angular velocity as pitch, yaw and roll.
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn gyro(&self) -> [i16; 3];
+
+    fn set_gyro(&mut self, gyro: [i16; 3]);
+
+    /// Acceleration along the x, y and z axis.
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn accel(&self) -> [i16; 3];
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn set_accel(&mut self, accel: [i16; 3]);
+}
@@ -0,0 +1,147 @@
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+use super::{
+    ds4::{DS4, USB_REPORT_SIZE},
+    ds4_feature::{self, DeviceIdentity},
+};
+
+/// Device path the virtual controller is enumerated under.
+/// It looks like a usb ds4 (v2) so `ControllerKind::from_device_path` picks it up as well.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const DEVICE_PATH: &str =
+    r"\\?\hid#vid_054c&pid_09cc&mi_03#mouseplay&0&0000#{4d1e55b2-f16f-11cf-88cb-001111000030}";
+
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const VENDOR_ID: u16 = 0x054C;
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const PRODUCT_ID: u16 = 0x09CC;
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const VERSION_NUMBER: u16 = 0x0100;
+
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const OUTPUT_REPORT_SIZE: usize = 32;
+#[cfg_attr(not(windows), allow(dead_code))]
+pub const FEATURE_REPORT_SIZE: usize = 64;
+
+// a usb ds4 sends a report every 4ms
+pub const REPORT_INTERVAL: Duration = Duration::from_millis(4);
+
+// the sensor timestamp counts in units of 16/3 microseconds
+const TIMESTAMP: usize = 10;
+const BATTERY: usize = 30;
+// cable connected and fully charged
+const BATTERY_CHARGED: u8 = 0x10 | 0x0A;
+
+const TOUCH_PACKETS: usize = 33;
+const TOUCH_INACTIVE: u8 = 1 << 7;
+
+lazy_static! {
+    // thread safe storage for the virtual controller, shared by all of its handles
+    pub static ref VIRTUAL_DS4: RwLock<VirtualDS4> = RwLock::new(VirtualDS4::new());
+}
+
+/// A DualShock 4 without hardware behind it, it produces usb input reports
+/// in which nothing is pressed so the mapper can fill them in.
+pub struct VirtualDS4 {
+    started: Instant,
+    last_report: Option<Instant>,
+    frame_count: u8,
+    #[cfg_attr(not(windows), allow(dead_code))]
+    identity: DeviceIdentity,
+}
+
+impl VirtualDS4 {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_report: None,
+            frame_count: 0,
+            identity: DeviceIdentity::default(),
+        }
+    }
+
+    /// Time left until the next report is due, reads are paced like the real device.
+    pub fn time_until_next_report(&self, now: Instant) -> Duration {
+        match self.last_report {
+            Some(last_report) => (last_report + REPORT_INTERVAL).saturating_duration_since(now),
+            None => Duration::from_millis(0),
+        }
+    }
+
+    /// Builds the next neutral input report.
+    pub fn next_report(&mut self, now: Instant) -> DS4 {
+        let mut buffer = vec![0u8; USB_REPORT_SIZE];
+        buffer[0] = 0x01;
+        // sticks centered
+        buffer[1..5].copy_from_slice(&[0x80; 4]);
+        // hat released, no face buttons
+        buffer[5] = 0x08;
+        // the frame counter lives in the upper six bits
+        buffer[7] = self.frame_count << 2;
+
+        let elapsed = now.saturating_duration_since(self.started);
+        let timestamp = (elapsed.as_micros() * 3 / 16) as u16;
+        buffer[TIMESTAMP..TIMESTAMP + 2].copy_from_slice(&timestamp.to_le_bytes());
+
+        // resting flat on a table, gravity pulls along the y axis
+        buffer[21..23].copy_from_slice(&8192i16.to_le_bytes());
+
+        buffer[BATTERY] = BATTERY_CHARGED;
+
+        // one touch packet with both fingers lifted
+        buffer[TOUCH_PACKETS] = 1;
+        buffer[TOUCH_PACKETS + 1] = self.frame_count;
+        buffer[TOUCH_PACKETS + 2] = TOUCH_INACTIVE;
+        buffer[TOUCH_PACKETS + 6] = TOUCH_INACTIVE;
+
+        self.frame_count = (self.frame_count + 1) % 64;
+        self.last_report = Some(now);
+
+        DS4::new(&buffer).unwrap()
+    }
+
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn set_identity(&mut self, identity: DeviceIdentity) {
+        self.identity = identity;
+    }
+
+    /// Answers a feature report request, `None` if the real device does not know the report.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn feature_report(&self, report_id: u8) -> Option<Vec<u8>> {
+        ds4_feature::feature_report(&self.identity, report_id)
+    }
+}
+
+impl Default for VirtualDS4 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{Axis, Button, Controller, DPad};
+
+    #[test]
+    fn neutral_report() {
+        let mut virtual_ds4 = VirtualDS4::new();
+        let now = Instant::now();
+        let ds4 = virtual_ds4.next_report(now);
+
+        assert_eq!(ds4.axis(Axis::Lx), 0x80);
+        assert_eq!(ds4.axis(Axis::Ry), 0x80);
+        assert_eq!(ds4.axis(Axis::L2), 0);
+        assert_eq!(ds4.dpad(), DPad::Released);
+        assert!(!ds4.btn(Button::Cross));
+        assert!(!ds4.touch_point(0).active);
+        assert_eq!(ds4.battery(), 100);
+
+        let next = virtual_ds4.next_report(now + REPORT_INTERVAL);
+        assert_eq!(next.frame_count(), ds4.frame_count() + 1);
+        assert_eq!(virtual_ds4.time_until_next_report(now), REPORT_INTERVAL * 2);
+    }
+}
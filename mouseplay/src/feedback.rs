@@ -0,0 +1,364 @@
+use std::time::{Duration, Instant};
+
+use log::info;
+use serde::Deserialize;
+
+use crate::controller::ds4_output::OutputState;
+
+/// Something the game's rumble got turned into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RumbleEvent {
+    /// The controller has been rumbling for at least the configured duration.
+    Started { intensity: f32 },
+    /// A rumble that has been reported as started is over.
+    Stopped { peak: f32, duration: Duration },
+}
+
+/// Receives rumble events, implement it to forward game feedback somewhere new.
+pub trait FeedbackSink: Send + Sync {
+    fn rumble(&mut self, event: RumbleEvent);
+}
+
+/// Writes a line to the console.
+pub struct LogSink;
+
+impl FeedbackSink for LogSink {
+    fn rumble(&mut self, event: RumbleEvent) {
+        match event {
+            RumbleEvent::Started { intensity } => {
+                info!("rumble started ({:.0}%)", intensity * 100f32)
+            }
+            RumbleEvent::Stopped { peak, duration } => info!(
+                "rumble stopped after {}ms ({:.0}% peak)",
+                duration.as_millis(),
+                peak * 100f32
+            ),
+        }
+    }
+}
+
+/// Plays a sound whenever a rumble starts, the system default sound if no file is given.
+pub struct SoundSink {
+    #[cfg_attr(not(windows), allow(dead_code))]
+    file: Option<String>,
+}
+
+impl FeedbackSink for SoundSink {
+    #[cfg(windows)]
+    fn rumble(&mut self, event: RumbleEvent) {
+        use winapi::um::{
+            playsoundapi::{PlaySoundW, SND_ASYNC, SND_FILENAME, SND_NODEFAULT},
+            winuser::{MessageBeep, MB_OK},
+        };
+
+        if let RumbleEvent::Started { .. } = event {
+            match &self.file {
+                Some(file) => {
+                    let file = file
+                        .encode_utf16()
+                        .chain(std::iter::once(0))
+                        .collect::<Vec<u16>>();
+                    unsafe {
+                        PlaySoundW(
+                            file.as_ptr(),
+                            std::ptr::null_mut(),
+                            SND_ASYNC | SND_FILENAME | SND_NODEFAULT,
+                        )
+                    };
+                }
+                None => unsafe {
+                    MessageBeep(MB_OK);
+                },
+            }
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn rumble(&mut self, _event: RumbleEvent) {}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+enum SinkConfig {
+    Log,
+    Sound {
+        #[serde(default)]
+        file: Option<String>,
+    },
+}
+
+impl SinkConfig {
+    fn into_sink(self) -> Box<dyn FeedbackSink> {
+        match self {
+            SinkConfig::Log => Box::new(LogSink),
+            SinkConfig::Sound { file } => Box::new(SoundSink { file }),
+        }
+    }
+}
+
+fn default_min_intensity() -> f32 {
+    0.1
+}
+
+/// The `feedback` section of a mappings file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeedbackConfig {
+    /// Rumble below this intensity (0 - 1) is ignored.
+    #[serde(default = "default_min_intensity")]
+    min_intensity: f32,
+    /// Rumble has to last this long in milliseconds before it is reported.
+    #[serde(default)]
+    min_duration: u64,
+    #[serde(default)]
+    sinks: Vec<SinkConfig>,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            min_intensity: default_min_intensity(),
+            min_duration: 0,
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl FeedbackConfig {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !(0f32..=1f32).contains(&self.min_intensity) {
+            return Err("min_intensity has to be between 0 and 1");
+        }
+        Ok(())
+    }
+
+    /// These settings with the ones a profile sets replaced.
+    pub fn merge(&self, profile: &FeedbackOverride) -> Self {
+        Self {
+            min_intensity: profile.min_intensity.unwrap_or(self.min_intensity),
+            min_duration: profile.min_duration.unwrap_or(self.min_duration),
+            sinks: profile.sinks.clone().unwrap_or_else(|| self.sinks.clone()),
+        }
+    }
+}
+
+/// The `feedback` section of a profile, everything it leaves out is taken
+/// from the `feedback` section of the mappings file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeedbackOverride {
+    #[serde(default)]
+    min_intensity: Option<f32>,
+    #[serde(default)]
+    min_duration: Option<u64>,
+    #[serde(default)]
+    sinks: Option<Vec<SinkConfig>>,
+}
+
+/// Turns the rumble the game sends to the controller into events for the configured sinks.
+pub struct Feedback {
+    min_intensity: f32,
+    min_duration: Duration,
+    sinks: Vec<Box<dyn FeedbackSink>>,
+
+    // internal state
+    started_at: Option<Instant>,
+    reported: bool,
+    peak: f32,
+}
+
+impl Default for Feedback {
+    fn default() -> Self {
+        FeedbackConfig::default().into()
+    }
+}
+
+impl From<FeedbackConfig> for Feedback {
+    fn from(config: FeedbackConfig) -> Self {
+        Self::new(
+            config.min_intensity,
+            Duration::from_millis(config.min_duration),
+            config
+                .sinks
+                .into_iter()
+                .map(SinkConfig::into_sink)
+                .collect(),
+        )
+    }
+}
+
+impl Feedback {
+    pub fn new(
+        min_intensity: f32,
+        min_duration: Duration,
+        sinks: Vec<Box<dyn FeedbackSink>>,
+    ) -> Self {
+        Self {
+            min_intensity,
+            min_duration,
+            sinks,
+            started_at: None,
+            reported: false,
+            peak: 0f32,
+        }
+    }
+
+    /// Rumble strength (0 - 1) of the stronger motor.
+    pub fn intensity(output_state: &OutputState) -> f32 {
+        output_state.rumble_large.max(output_state.rumble_small) as f32 / 255f32
+    }
+
+    /// Reports the end of a started rumble, e.g. when another profile takes over the feedback.
+    pub fn stop(&mut self, now: Instant) {
+        self.update(&OutputState::default(), now);
+    }
+
+    /// Has to be called regularly, rumble durations are only as accurate as the calls.
+    pub fn update(&mut self, output_state: &OutputState, now: Instant) {
+        let intensity = Self::intensity(output_state);
+
+        let event = if intensity > 0f32 && intensity >= self.min_intensity {
+            let started_at = *self.started_at.get_or_insert(now);
+            self.peak = self.peak.max(intensity);
+            if !self.reported && now.duration_since(started_at) >= self.min_duration {
+                self.reported = true;
+                Some(RumbleEvent::Started {
+                    intensity: self.peak,
+                })
+            } else {
+                None
+            }
+        } else {
+            let event = match self.started_at {
+                Some(started_at) if self.reported => Some(RumbleEvent::Stopped {
+                    peak: self.peak,
+                    duration: now.duration_since(started_at),
+                }),
+                _ => None,
+            };
+            self.started_at = None;
+            self.reported = false;
+            self.peak = 0f32;
+            event
+        };
+
+        if let Some(event) = event {
+            for sink in self.sinks.iter_mut() {
+                sink.rumble(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingSink(Arc<Mutex<Vec<RumbleEvent>>>);
+
+    impl FeedbackSink for RecordingSink {
+        fn rumble(&mut self, event: RumbleEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    fn rumble(large: u8) -> OutputState {
+        OutputState {
+            rumble_large: large,
+            ..OutputState::default()
+        }
+    }
+
+    fn feedback(min_intensity: f32, min_duration: u64) -> (Feedback, Arc<Mutex<Vec<RumbleEvent>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let feedback = Feedback::new(
+            min_intensity,
+            Duration::from_millis(min_duration),
+            vec![Box::new(RecordingSink(events.clone()))],
+        );
+        (feedback, events)
+    }
+
+    #[test]
+    fn start_and_stop() {
+        let (mut feedback, events) = feedback(0.1, 0);
+        let now = Instant::now();
+
+        feedback.update(&rumble(255), now);
+        feedback.update(&rumble(255), now + Duration::from_millis(100));
+        feedback.update(&rumble(0), now + Duration::from_millis(200));
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                RumbleEvent::Started { intensity: 1f32 },
+                RumbleEvent::Stopped {
+                    peak: 1f32,
+                    duration: Duration::from_millis(200)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn weak_rumble_is_ignored() {
+        let (mut feedback, events) = feedback(0.5, 0);
+        let now = Instant::now();
+
+        feedback.update(&rumble(64), now);
+        feedback.update(&rumble(0), now + Duration::from_millis(100));
+
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn short_rumble_is_ignored() {
+        let (mut feedback, events) = feedback(0.1, 50);
+        let now = Instant::now();
+
+        feedback.update(&rumble(255), now);
+        feedback.update(&rumble(255), now + Duration::from_millis(20));
+        feedback.update(&rumble(0), now + Duration::from_millis(40));
+        assert!(events.lock().unwrap().is_empty());
+
+        feedback.update(&rumble(128), now + Duration::from_millis(100));
+        feedback.update(&rumble(255), now + Duration::from_millis(150));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![RumbleEvent::Started { intensity: 1f32 }]
+        );
+    }
+    #[test]
+    fn profile_overrides() {
+        let file = serde_json::from_str::<FeedbackConfig>(
+            r#"{ "min_intensity": 0.3, "min_duration": 40, "sinks": [{ "type": "Log" }] }"#,
+        )
+        .unwrap();
+        let profile =
+            serde_json::from_str::<FeedbackOverride>(r#"{ "min_intensity": 0.6 }"#).unwrap();
+        let merged = file.merge(&profile);
+        assert_eq!(merged.min_intensity, 0.6);
+        assert_eq!(merged.min_duration, 40);
+        assert_eq!(merged.sinks.len(), 1);
+
+        let profile = serde_json::from_str::<FeedbackOverride>(r#"{ "sinks": [] }"#).unwrap();
+        assert!(file.merge(&profile).sinks.is_empty());
+    }
+
+    #[test]
+    fn stop_ends_a_started_rumble() {
+        let (mut feedback, events) = feedback(0.1, 0);
+        let now = Instant::now();
+
+        feedback.update(&rumble(255), now);
+        feedback.stop(now + Duration::from_millis(30));
+        assert_eq!(
+            events.lock().unwrap()[1],
+            RumbleEvent::Stopped {
+                peak: 1f32,
+                duration: Duration::from_millis(30)
+            }
+        );
+    }
+}
@@ -1,19 +1,46 @@
-use crate::controller::ds4::DS4;
+use crate::controller::{
+    ds4::{BLUETOOTH_REPORT_SIZE, DS4, USB_REPORT_SIZE},
+    ds4_output::{DS4OutputReport, OUTPUT_STATE},
+    ds5::DS5,
+    virtual_ds4::{self, VIRTUAL_DS4},
+    Controller, ControllerKind,
+};
 
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::ffi::{c_void, CStr, CString};
 use std::mem::size_of;
+use std::sync::RwLock;
+use std::time::Instant;
+
+use log::{info, warn};
 
-use log::info;
+use lazy_static::lazy_static;
 
-use libc::strcmp;
 use winapi::{
-    shared::minwindef::LPCVOID,
+    shared::{
+        guiddef::GUID,
+        hidclass::{GUID_DEVINTERFACE_HID, IOCTL_HID_GET_FEATURE},
+        hidpi::{
+            HIDP_CAPS, HIDP_STATUS_INVALID_PREPARSED_DATA, HIDP_STATUS_SUCCESS, PHIDP_CAPS,
+            PHIDP_PREPARSED_DATA,
+        },
+        hidsdi::PHIDD_ATTRIBUTES,
+        minwindef::{FALSE, LPCVOID, PDWORD, TRUE, ULONG},
+        ntdef::{BOOLEAN, NTSTATUS},
+        winerror::{ERROR_INSUFFICIENT_BUFFER, ERROR_NO_MORE_ITEMS},
+    },
     um::{
+        errhandlingapi::{GetLastError, SetLastError},
         handleapi::INVALID_HANDLE_VALUE,
         libloaderapi::GetModuleHandleA,
         memoryapi::VirtualProtect,
         minwinbase::LPSECURITY_ATTRIBUTES,
+        setupapi::{
+            HDEVINFO, PSP_DEVICE_INTERFACE_DATA, PSP_DEVICE_INTERFACE_DETAIL_DATA_W,
+            PSP_DEVINFO_DATA, SPINT_ACTIVE,
+        },
+        synchapi::{CreateEventW, SetEvent},
         winnt::{
             LPCWSTR, PAGE_EXECUTE_READWRITE, PIMAGE_DOS_HEADER, PIMAGE_IMPORT_BY_NAME,
             PIMAGE_IMPORT_DESCRIPTOR, PIMAGE_NT_HEADERS, PIMAGE_THUNK_DATA,
@@ -28,6 +55,32 @@ use winapi::{
     um::minwinbase::LPOVERLAPPED,
 };
 
+lazy_static! {
+    // thread safe storage for the kind of controller behind each opened device handle
+    static ref CONTROLLER_HANDLES: RwLock<HashMap<u64, ControllerKind>> =
+        RwLock::new(HashMap::new());
+
+    // handles that were opened for the virtual controller
+    static ref VIRTUAL_HANDLES: RwLock<HashSet<u64>> = RwLock::new(HashSet::new());
+
+    // state of each running hid device enumeration, keyed by the device info set
+    static ref ENUMERATIONS: RwLock<HashMap<u64, Enumeration>> = RwLock::new(HashMap::new());
+}
+
+#[derive(Default)]
+struct Enumeration {
+    // a physical controller has been handed out, no need to spoof one
+    controller_found: bool,
+    // member index the virtual controller was appended at
+    virtual_index: Option<DWORD>,
+}
+
+// marks the interface data of the virtual controller, the real value points to setupapi internals
+const VIRTUAL_INTERFACE: usize = 0x6D70_6C79;
+
+// handed out as preparsed data of the virtual controller, it is never dereferenced
+static VIRTUAL_PREPARSED_DATA: u8 = 0;
+
 unsafe fn hook_import(
     target_module: &str,
     import_module: &str,
@@ -61,10 +114,12 @@ unsafe fn hook_import(
         + (*p_nt_headers).OptionalHeader.DataDirectory[1].VirtualAddress as u32)
         as PIMAGE_IMPORT_DESCRIPTOR; // PEIMAGE_DIRECTORY_ENTRY_IMPORT
     while *(*p_import_descriptor).u.OriginalFirstThunk() != 0 {
-        if strcmp(
-            import_module_cstr.as_ptr(),
-            (target_module_base + (*p_import_descriptor).Name as u32) as *const i8,
-        ) == 0
+        // module names are not consistently cased in import tables
+        let descriptor_name =
+            CStr::from_ptr((target_module_base + (*p_import_descriptor).Name as u32) as *const i8);
+        if descriptor_name
+            .to_bytes()
+            .eq_ignore_ascii_case(import_module_cstr.as_bytes())
         {
             // iterate funcs
             let mut p_thunk = (target_module_base + (*p_import_descriptor).FirstThunk as u32)
@@ -146,6 +201,28 @@ unsafe extern "stdcall" fn hook_create_file(
     //info!("hook_create_file(): lp_file_name={:?}, dw_desired_access={:?}, dw_share_mode={:?}, lp_security_attributes={:?}, dw_creation_disposition={:?}, dw_flags_and_attributes={:?}, h_template_file={:?}",
     //  lp_file_name, dw_desired_access, dw_share_mode, lp_security_attributes, dw_creation_disposition, dw_flags_and_attributes, h_template_file);
 
+    let lp_file_name_str = u16_ptr_to_string(lp_file_name);
+
+    // the virtual controller has no device behind it, an event object stands in for the handle
+    if lp_file_name_str
+        .to_string_lossy()
+        .eq_ignore_ascii_case(virtual_ds4::DEVICE_PATH)
+    {
+        let handle = CreateEventW(std::ptr::null_mut(), TRUE, FALSE, std::ptr::null());
+        if handle.is_null() {
+            return INVALID_HANDLE_VALUE;
+        }
+
+        info!("opened virtual controller");
+        if let Ok(mut handles) = VIRTUAL_HANDLES.write() {
+            handles.insert(handle as u64);
+        }
+        if let Ok(mut handles) = CONTROLLER_HANDLES.write() {
+            handles.insert(handle as u64, ControllerKind::DualShock4);
+        }
+        return handle;
+    }
+
     let orig_func: extern "stdcall" fn(
         _: LPCWSTR,
         _: DWORD,
@@ -165,7 +242,23 @@ unsafe extern "stdcall" fn hook_create_file(
         h_template_file,
     );
 
-    let lp_file_name_str = u16_ptr_to_string(lp_file_name);
+    if result != INVALID_HANDLE_VALUE {
+        if let Ok(mut handles) = VIRTUAL_HANDLES.write() {
+            handles.remove(&(result as u64));
+        }
+        if let Ok(mut handles) = CONTROLLER_HANDLES.write() {
+            match ControllerKind::from_device_path(&lp_file_name_str.to_string_lossy()) {
+                Some(kind) => {
+                    info!("opened {:?} controller at {:?}", kind, lp_file_name_str);
+                    handles.insert(result as u64, kind);
+                }
+                None => {
+                    // handle values are re-used after they have been closed
+                    handles.remove(&(result as u64));
+                }
+            }
+        }
+    }
 
     result
 }
@@ -184,20 +277,30 @@ unsafe extern "stdcall" fn hook_read_file(
     let mut bytes_read = 0;
 
     // only call original func if we are not spoofing a controller presence
-    let orig_func: extern "stdcall" fn(
-        _: HANDLE,
-        _: LPVOID,
-        _: DWORD,
-        _: LPDWORD,
-        _: LPOVERLAPPED,
-    ) -> BOOL = std::mem::transmute(ORIG_READ_FILE);
-    let result = orig_func(
-        h_file,
-        lp_buffer,
-        n_number_of_bytes_to_read,
-        &mut bytes_read as _,
-        lp_overlapped,
-    );
+    let result = if is_virtual_handle(h_file) {
+        if n_number_of_bytes_to_read as usize >= USB_REPORT_SIZE {
+            let report = read_virtual_report();
+            std::ptr::copy_nonoverlapping(report.as_ptr(), lp_buffer as *mut u8, report.len());
+            bytes_read = report.len() as DWORD;
+        }
+        complete_virtual_io(lp_overlapped, bytes_read);
+        TRUE
+    } else {
+        let orig_func: extern "stdcall" fn(
+            _: HANDLE,
+            _: LPVOID,
+            _: DWORD,
+            _: LPDWORD,
+            _: LPOVERLAPPED,
+        ) -> BOOL = std::mem::transmute(ORIG_READ_FILE);
+        orig_func(
+            h_file,
+            lp_buffer,
+            n_number_of_bytes_to_read,
+            &mut bytes_read as _,
+            lp_overlapped,
+        )
+    };
 
     if !lp_number_of_bytes_read.is_null() {
         *lp_number_of_bytes_read = bytes_read;
@@ -206,21 +309,39 @@ unsafe extern "stdcall" fn hook_read_file(
     // hijack wndproc
     crate::input::raw_input::hijack_wndproc().unwrap();
 
-    // TODO: figure out if we are in ds4 or ds5 mode
+    // handles we did not see being opened are treated as ds4 controllers
+    let kind = CONTROLLER_HANDLES
+        .read()
+        .ok()
+        .and_then(|handles| handles.get(&(h_file as u64)).copied())
+        .unwrap_or(ControllerKind::DualShock4);
 
     // try to find a request with 64 bytes to determine the controller handle
     let buffer = std::slice::from_raw_parts_mut(lp_buffer as *mut u8, bytes_read as usize);
-    if bytes_read >= 64 && bytes_read % 64 == 0 {
+    if kind == ControllerKind::DualShock4
+        && buffer.len() >= BLUETOOTH_REPORT_SIZE
+        && buffer[0] == 0x11
+    {
+        // bluetooth reads are padded to the largest input report, the report itself comes first
+        let report = &mut buffer[..BLUETOOTH_REPORT_SIZE];
+        if let Ok(mut ds4) = DS4::new(report) {
+            map_controller(&mut ds4);
+            report.copy_from_slice(ds4.to_raw().as_slice());
+        }
+    } else if bytes_read >= 64 && bytes_read % 64 == 0 {
         // sometimes a request can contain multiple reports
         for buffer_part in buffer.chunks_exact_mut(64) {
-            if let Ok(mut ds4) = DS4::new(buffer_part) {
-                if let Ok(mut raw_input) = crate::input::raw_input::RAW_INPUT.write() {
-                    if let Ok(mut mapper) = crate::mapper::MAPPER.write() {
-                        if let Some(mapper) = mapper.as_mut() {
-                            raw_input.accumulate();
-                            mapper.map_controller(&raw_input, &mut ds4);
-                            buffer_part.copy_from_slice(ds4.to_raw().as_slice());
-                        }
+            match kind {
+                ControllerKind::DualShock4 => {
+                    if let Ok(mut ds4) = DS4::new(buffer_part) {
+                        map_controller(&mut ds4);
+                        buffer_part.copy_from_slice(ds4.to_raw().as_slice());
+                    }
+                }
+                ControllerKind::DualSense => {
+                    if let Ok(mut ds5) = DS5::new(buffer_part) {
+                        map_controller(&mut ds5);
+                        buffer_part.copy_from_slice(ds5.to_raw().as_slice());
                     }
                 }
             }
@@ -230,6 +351,55 @@ unsafe extern "stdcall" fn hook_read_file(
     result
 }
 
+fn is_virtual_handle(handle: HANDLE) -> bool {
+    VIRTUAL_HANDLES
+        .read()
+        .map(|handles| handles.contains(&(handle as u64)))
+        .unwrap_or(false)
+}
+
+// blocks until the next report is due, just like reading from the real device would
+fn read_virtual_report() -> Vec<u8> {
+    let delay = VIRTUAL_DS4
+        .read()
+        .map(|virtual_ds4| virtual_ds4.time_until_next_report(Instant::now()))
+        .unwrap_or_default();
+    std::thread::sleep(delay);
+
+    match VIRTUAL_DS4.write() {
+        Ok(mut virtual_ds4) => virtual_ds4.next_report(Instant::now()).to_raw(),
+        Err(_) => vec![0u8; USB_REPORT_SIZE],
+    }
+}
+
+// virtual io completes immediately, overlapped callers still wait on the event
+unsafe fn complete_virtual_io(lp_overlapped: LPOVERLAPPED, bytes_transferred: DWORD) {
+    if !lp_overlapped.is_null() {
+        (*lp_overlapped).Internal = 0; // STATUS_SUCCESS
+        (*lp_overlapped).InternalHigh = bytes_transferred as _;
+        if !(*lp_overlapped).hEvent.is_null() {
+            SetEvent((*lp_overlapped).hEvent);
+        }
+    }
+}
+
+fn map_controller(controller: &mut dyn Controller) {
+    if let Ok(mut raw_input) = crate::input::raw_input::RAW_INPUT.write() {
+        if let Ok(mut mapper) = crate::mapper::MAPPER.write() {
+            if let Some(mapper) = mapper.as_mut() {
+                raw_input.accumulate();
+                raw_input.update_wheel(mapper.wheel_pulse(), Instant::now());
+                mapper.map_controller(&raw_input, controller);
+
+                // reads come in at a steady rate, which keeps rumble durations accurate
+                if let Ok(output_state) = OUTPUT_STATE.read() {
+                    mapper.update_output(&output_state, Instant::now());
+                }
+            }
+        }
+    }
+}
+
 static mut ORIG_WRITE_FILE: *const c_void = std::ptr::null_mut();
 unsafe extern "stdcall" fn hook_write_file(
     h_file: HANDLE,
@@ -241,51 +411,381 @@ unsafe extern "stdcall" fn hook_write_file(
     //info!("hook_write_file(): h_file={:?}, lp_bufer={:?}, n_number_of_bytes_to_write={:?}, lp_number_of_bytes_written={:?}, lp_overlapped={:?}",
     //  h_file, lp_buffer, n_number_of_bytes_to_write, lp_number_of_bytes_written, lp_overlapped);
 
-    /*
-    if n_number_of_bytes_to_write == 32 {
-        let slice = std::slice::from_raw_parts_mut(
-            lp_buffer as *mut u8,
-            n_number_of_bytes_to_write as usize,
-        );
-        //slice[1] = 5;
-        slice[4] = 100; // rumble
-                        //slice[8] = 5; // rumble time?
+    // overlapped writes may still be pending, the report is known up front either way
+    let kind = CONTROLLER_HANDLES
+        .read()
+        .ok()
+        .and_then(|handles| handles.get(&(h_file as u64)).copied())
+        .unwrap_or(ControllerKind::DualShock4);
+    if kind == ControllerKind::DualShock4 && !lp_buffer.is_null() {
+        let buffer =
+            std::slice::from_raw_parts(lp_buffer as *const u8, n_number_of_bytes_to_write as usize);
+        if let Ok(report) = DS4OutputReport::new(buffer) {
+            if let Ok(mut output_state) = OUTPUT_STATE.write() {
+                output_state.apply(&report, Instant::now());
+            }
+        }
     }
-    */
 
     let mut bytes_written = 0;
+    let result = if is_virtual_handle(h_file) {
+        // there is nothing to rumble or light up
+        bytes_written = n_number_of_bytes_to_write;
+        complete_virtual_io(lp_overlapped, bytes_written);
+        TRUE
+    } else {
+        let orig_func: extern "stdcall" fn(
+            _: HANDLE,
+            _: LPCVOID,
+            _: DWORD,
+            _: LPDWORD,
+            _: LPOVERLAPPED,
+        ) -> BOOL = std::mem::transmute(ORIG_WRITE_FILE);
+        orig_func(
+            h_file,
+            lp_buffer,
+            n_number_of_bytes_to_write,
+            &mut bytes_written as _,
+            lp_overlapped,
+        )
+    };
+    if !lp_number_of_bytes_written.is_null() {
+        *lp_number_of_bytes_written = bytes_written;
+    }
+
+    result
+}
+
+static mut ORIG_CLOSE_HANDLE: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_close_handle(h_object: HANDLE) -> BOOL {
+    if let Ok(mut handles) = VIRTUAL_HANDLES.write() {
+        handles.remove(&(h_object as u64));
+    }
+    if let Ok(mut handles) = CONTROLLER_HANDLES.write() {
+        handles.remove(&(h_object as u64));
+    }
+
+    let orig_func: extern "stdcall" fn(_: HANDLE) -> BOOL = std::mem::transmute(ORIG_CLOSE_HANDLE);
+    orig_func(h_object)
+}
+
+// copies the feature report requested in the first byte of the buffer
+unsafe fn get_virtual_feature(buffer: *mut u8, buffer_len: usize) -> Option<usize> {
+    if buffer.is_null() || buffer_len == 0 {
+        return None;
+    }
+
+    let report = VIRTUAL_DS4.read().ok()?.feature_report(*buffer)?;
+    let len = report.len().min(buffer_len);
+    std::ptr::copy_nonoverlapping(report.as_ptr(), buffer, len);
+    Some(len)
+}
+
+static mut ORIG_DEVICE_IO_CONTROL: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_device_io_control(
+    h_device: HANDLE,
+    dw_io_control_code: DWORD,
+    lp_in_buffer: LPVOID,
+    n_in_buffer_size: DWORD,
+    lp_out_buffer: LPVOID,
+    n_out_buffer_size: DWORD,
+    lp_bytes_returned: LPDWORD,
+    lp_overlapped: LPOVERLAPPED,
+) -> BOOL {
+    if is_virtual_handle(h_device) {
+        let mut bytes_returned = 0;
+        if dw_io_control_code == IOCTL_HID_GET_FEATURE {
+            match get_virtual_feature(lp_out_buffer as *mut u8, n_out_buffer_size as usize) {
+                Some(len) => bytes_returned = len as DWORD,
+                None => return FALSE,
+            }
+        }
+
+        if !lp_bytes_returned.is_null() {
+            *lp_bytes_returned = bytes_returned;
+        }
+        complete_virtual_io(lp_overlapped, bytes_returned);
+        return TRUE;
+    }
+
     let orig_func: extern "stdcall" fn(
         _: HANDLE,
-        _: LPCVOID,
+        _: DWORD,
+        _: LPVOID,
+        _: DWORD,
+        _: LPVOID,
         _: DWORD,
         _: LPDWORD,
         _: LPOVERLAPPED,
-    ) -> BOOL = std::mem::transmute(ORIG_WRITE_FILE);
-    let result = orig_func(
-        h_file,
-        lp_buffer,
-        n_number_of_bytes_to_write,
-        &mut bytes_written as _,
+    ) -> BOOL = std::mem::transmute(ORIG_DEVICE_IO_CONTROL);
+    orig_func(
+        h_device,
+        dw_io_control_code,
+        lp_in_buffer,
+        n_in_buffer_size,
+        lp_out_buffer,
+        n_out_buffer_size,
+        lp_bytes_returned,
         lp_overlapped,
+    )
+}
+
+static mut ORIG_HIDD_GET_ATTRIBUTES: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_hidd_get_attributes(
+    hid_device_object: HANDLE,
+    attributes: PHIDD_ATTRIBUTES,
+) -> BOOLEAN {
+    if is_virtual_handle(hid_device_object) {
+        (*attributes).VendorID = virtual_ds4::VENDOR_ID;
+        (*attributes).ProductID = virtual_ds4::PRODUCT_ID;
+        (*attributes).VersionNumber = virtual_ds4::VERSION_NUMBER;
+        return TRUE as BOOLEAN;
+    }
+
+    let orig_func: extern "stdcall" fn(_: HANDLE, _: PHIDD_ATTRIBUTES) -> BOOLEAN =
+        std::mem::transmute(ORIG_HIDD_GET_ATTRIBUTES);
+    orig_func(hid_device_object, attributes)
+}
+
+static mut ORIG_HIDD_GET_FEATURE: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_hidd_get_feature(
+    hid_device_object: HANDLE,
+    report_buffer: LPVOID,
+    report_buffer_length: ULONG,
+) -> BOOLEAN {
+    if is_virtual_handle(hid_device_object) {
+        return get_virtual_feature(report_buffer as *mut u8, report_buffer_length as usize)
+            .is_some() as BOOLEAN;
+    }
+
+    let orig_func: extern "stdcall" fn(_: HANDLE, _: LPVOID, _: ULONG) -> BOOLEAN =
+        std::mem::transmute(ORIG_HIDD_GET_FEATURE);
+    orig_func(hid_device_object, report_buffer, report_buffer_length)
+}
+
+fn virtual_preparsed_data() -> PHIDP_PREPARSED_DATA {
+    &VIRTUAL_PREPARSED_DATA as *const u8 as PHIDP_PREPARSED_DATA
+}
+
+static mut ORIG_HIDD_GET_PREPARSED_DATA: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_hidd_get_preparsed_data(
+    hid_device_object: HANDLE,
+    preparsed_data: *mut PHIDP_PREPARSED_DATA,
+) -> BOOLEAN {
+    if is_virtual_handle(hid_device_object) {
+        *preparsed_data = virtual_preparsed_data();
+        return TRUE as BOOLEAN;
+    }
+
+    let orig_func: extern "stdcall" fn(_: HANDLE, _: *mut PHIDP_PREPARSED_DATA) -> BOOLEAN =
+        std::mem::transmute(ORIG_HIDD_GET_PREPARSED_DATA);
+    orig_func(hid_device_object, preparsed_data)
+}
+
+static mut ORIG_HIDD_FREE_PREPARSED_DATA: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_hidd_free_preparsed_data(
+    preparsed_data: PHIDP_PREPARSED_DATA,
+) -> BOOLEAN {
+    if preparsed_data == virtual_preparsed_data() {
+        return TRUE as BOOLEAN;
+    }
+
+    let orig_func: extern "stdcall" fn(_: PHIDP_PREPARSED_DATA) -> BOOLEAN =
+        std::mem::transmute(ORIG_HIDD_FREE_PREPARSED_DATA);
+    orig_func(preparsed_data)
+}
+
+static mut ORIG_HIDP_GET_CAPS: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_hidp_get_caps(
+    preparsed_data: PHIDP_PREPARSED_DATA,
+    capabilities: PHIDP_CAPS,
+) -> NTSTATUS {
+    if preparsed_data == virtual_preparsed_data() {
+        if capabilities.is_null() {
+            return HIDP_STATUS_INVALID_PREPARSED_DATA;
+        }
+
+        let mut caps: HIDP_CAPS = std::mem::zeroed();
+        caps.UsagePage = 0x01; // generic desktop
+        caps.Usage = 0x05; // game pad
+        caps.InputReportByteLength = USB_REPORT_SIZE as u16;
+        caps.OutputReportByteLength = virtual_ds4::OUTPUT_REPORT_SIZE as u16;
+        caps.FeatureReportByteLength = virtual_ds4::FEATURE_REPORT_SIZE as u16;
+        caps.NumberLinkCollectionNodes = 1;
+        *capabilities = caps;
+        return HIDP_STATUS_SUCCESS;
+    }
+
+    let orig_func: extern "stdcall" fn(_: PHIDP_PREPARSED_DATA, _: PHIDP_CAPS) -> NTSTATUS =
+        std::mem::transmute(ORIG_HIDP_GET_CAPS);
+    orig_func(preparsed_data, capabilities)
+}
+
+fn is_hid_interface(guid: &GUID) -> bool {
+    guid.Data1 == GUID_DEVINTERFACE_HID.Data1
+        && guid.Data2 == GUID_DEVINTERFACE_HID.Data2
+        && guid.Data3 == GUID_DEVINTERFACE_HID.Data3
+        && guid.Data4 == GUID_DEVINTERFACE_HID.Data4
+}
+
+static mut ORIG_SETUP_DI_ENUM_DEVICE_INTERFACES: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_setup_di_enum_device_interfaces(
+    device_info_set: HDEVINFO,
+    device_info_data: PSP_DEVINFO_DATA,
+    interface_class_guid: *const GUID,
+    member_index: DWORD,
+    device_interface_data: PSP_DEVICE_INTERFACE_DATA,
+) -> BOOL {
+    let orig_func: extern "stdcall" fn(
+        _: HDEVINFO,
+        _: PSP_DEVINFO_DATA,
+        _: *const GUID,
+        _: DWORD,
+        _: PSP_DEVICE_INTERFACE_DATA,
+    ) -> BOOL = std::mem::transmute(ORIG_SETUP_DI_ENUM_DEVICE_INTERFACES);
+    let result = orig_func(
+        device_info_set,
+        device_info_data,
+        interface_class_guid,
+        member_index,
+        device_interface_data,
     );
-    if !lp_number_of_bytes_written.is_null() {
-        *lp_number_of_bytes_written = bytes_written;
+
+    if result != FALSE
+        || GetLastError() != ERROR_NO_MORE_ITEMS
+        || interface_class_guid.is_null()
+        || !is_hid_interface(&*interface_class_guid)
+        || device_interface_data.is_null()
+    {
+        return result;
+    }
+
+    // append the virtual controller once all physical devices have been listed
+    if let Ok(mut enumerations) = ENUMERATIONS.write() {
+        let enumeration = enumerations.entry(device_info_set as u64).or_default();
+        if enumeration.controller_found {
+            return result;
+        }
+
+        let virtual_index = *enumeration.virtual_index.get_or_insert_with(|| {
+            info!("no controller connected, adding virtual controller");
+            member_index
+        });
+        if member_index == virtual_index {
+            (*device_interface_data).InterfaceClassGuid = *interface_class_guid;
+            (*device_interface_data).Flags = SPINT_ACTIVE;
+            (*device_interface_data).Reserved = VIRTUAL_INTERFACE;
+            SetLastError(0);
+            return TRUE;
+        }
+    }
+
+    SetLastError(ERROR_NO_MORE_ITEMS);
+    result
+}
+
+static mut ORIG_SETUP_DI_GET_DEVICE_INTERFACE_DETAIL: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_setup_di_get_device_interface_detail(
+    device_info_set: HDEVINFO,
+    device_interface_data: PSP_DEVICE_INTERFACE_DATA,
+    device_interface_detail_data: PSP_DEVICE_INTERFACE_DETAIL_DATA_W,
+    device_interface_detail_data_size: DWORD,
+    required_size: PDWORD,
+    device_info_data: PSP_DEVINFO_DATA,
+) -> BOOL {
+    if !device_interface_data.is_null() && (*device_interface_data).Reserved == VIRTUAL_INTERFACE {
+        // cbSize followed by the null terminated device path
+        let path = virtual_ds4::DEVICE_PATH
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect::<Vec<u16>>();
+        let size = (size_of::<DWORD>() + path.len() * size_of::<u16>()) as DWORD;
+        if !required_size.is_null() {
+            *required_size = size;
+        }
+        if device_interface_detail_data.is_null() || device_interface_detail_data_size < size {
+            SetLastError(ERROR_INSUFFICIENT_BUFFER);
+            return FALSE;
+        }
+
+        std::ptr::copy_nonoverlapping(
+            path.as_ptr() as *const u8,
+            (device_interface_detail_data as *mut u8).add(size_of::<DWORD>()),
+            path.len() * size_of::<u16>(),
+        );
+        return TRUE;
     }
 
-    if bytes_written == 32 {
-        let slice = std::slice::from_raw_parts(lp_buffer as *mut u8, bytes_written as usize);
-        //info!("written: {:?}", slice);
+    let orig_func: extern "stdcall" fn(
+        _: HDEVINFO,
+        _: PSP_DEVICE_INTERFACE_DATA,
+        _: PSP_DEVICE_INTERFACE_DETAIL_DATA_W,
+        _: DWORD,
+        _: PDWORD,
+        _: PSP_DEVINFO_DATA,
+    ) -> BOOL = std::mem::transmute(ORIG_SETUP_DI_GET_DEVICE_INTERFACE_DETAIL);
+    let result = orig_func(
+        device_info_set,
+        device_interface_data,
+        device_interface_detail_data,
+        device_interface_detail_data_size,
+        required_size,
+        device_info_data,
+    );
+
+    if result != FALSE && !device_interface_detail_data.is_null() {
+        let device_path = u16_ptr_to_string(
+            (device_interface_detail_data as *const u8).add(size_of::<DWORD>()) as *const u16,
+        );
+        if ControllerKind::from_device_path(&device_path.to_string_lossy()).is_some() {
+            if let Ok(mut enumerations) = ENUMERATIONS.write() {
+                enumerations
+                    .entry(device_info_set as u64)
+                    .or_default()
+                    .controller_found = true;
+            }
+        }
     }
 
     result
 }
 
+static mut ORIG_SETUP_DI_DESTROY_DEVICE_INFO_LIST: *const c_void = std::ptr::null_mut();
+unsafe extern "stdcall" fn hook_setup_di_destroy_device_info_list(
+    device_info_set: HDEVINFO,
+) -> BOOL {
+    if let Ok(mut enumerations) = ENUMERATIONS.write() {
+        enumerations.remove(&(device_info_set as u64));
+    }
+
+    let orig_func: extern "stdcall" fn(_: HDEVINFO) -> BOOL =
+        std::mem::transmute(ORIG_SETUP_DI_DESTROY_DEVICE_INFO_LIST);
+    orig_func(device_info_set)
+}
+
+// hooks which are only needed for the virtual controller, clients that do not import
+// a function never call it so a missing import is not an error
+unsafe fn hook_optional_import(
+    import_module: &str,
+    import_name: &str,
+    hook_func: *mut c_void,
+) -> *const c_void {
+    match hook_import("RpCtrlWrapper.dll", import_module, import_name, hook_func) {
+        Ok(orig_func) => orig_func,
+        Err(err) => {
+            warn!("unable to hook {}!{}: {}", import_module, import_name, err);
+            std::ptr::null_mut()
+        }
+    }
+}
+
 pub fn setup() {
     // hook:
     // RpCtrlWrapper.dll", "KERNEL32.dll", "IsDebuggerPresent"
     // RpCtrlWrapper.dll", "KERNEL32.dll", "CreateFileW"
     // RpCtrlWrapper.dll", "KERNEL32.dll", "ReadFile"
     // RpCtrlWrapper.dll", "KERNEL32.dll", "WriteFile"
+    // plus the device enumeration and hid functions for the virtual controller
 
     unsafe {
         ORIG_IS_DEBUGGER_PRESENT = hook_import(
@@ -323,4 +823,51 @@ pub fn setup() {
         )
         .unwrap();
     }
+
+    info!("hooking functions used by the virtual controller");
+    unsafe {
+        ORIG_CLOSE_HANDLE =
+            hook_optional_import("KERNEL32.dll", "CloseHandle", hook_close_handle as _);
+        ORIG_DEVICE_IO_CONTROL = hook_optional_import(
+            "KERNEL32.dll",
+            "DeviceIoControl",
+            hook_device_io_control as _,
+        );
+
+        ORIG_HIDD_GET_ATTRIBUTES = hook_optional_import(
+            "HID.DLL",
+            "HidD_GetAttributes",
+            hook_hidd_get_attributes as _,
+        );
+        ORIG_HIDD_GET_FEATURE =
+            hook_optional_import("HID.DLL", "HidD_GetFeature", hook_hidd_get_feature as _);
+        ORIG_HIDD_GET_PREPARSED_DATA = hook_optional_import(
+            "HID.DLL",
+            "HidD_GetPreparsedData",
+            hook_hidd_get_preparsed_data as _,
+        );
+        ORIG_HIDD_FREE_PREPARSED_DATA = hook_optional_import(
+            "HID.DLL",
+            "HidD_FreePreparsedData",
+            hook_hidd_free_preparsed_data as _,
+        );
+        ORIG_HIDP_GET_CAPS =
+            hook_optional_import("HID.DLL", "HidP_GetCaps", hook_hidp_get_caps as _);
+
+        ORIG_SETUP_DI_ENUM_DEVICE_INTERFACES = hook_optional_import(
+            "SETUPAPI.dll",
+            "SetupDiEnumDeviceInterfaces",
+            hook_setup_di_enum_device_interfaces as _,
+        );
+        ORIG_SETUP_DI_GET_DEVICE_INTERFACE_DETAIL = hook_optional_import(
+            "SETUPAPI.dll",
+            "SetupDiGetDeviceInterfaceDetailW",
+            hook_setup_di_get_device_interface_detail as _,
+        );
+        ORIG_SETUP_DI_DESTROY_DEVICE_INFO_LIST = hook_optional_import(
+            "SETUPAPI.dll",
+            "SetupDiDestroyDeviceInfoList",
+            hook_setup_di_destroy_device_info_list as _,
+        );
+    }
 }
@@ -0,0 +1,149 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::{key::Key, raw_input::RawInput};
+
+/// One or more keys that have to be held down together.
+///
+/// Chords are written as `"shift+e"` or as a list of keys like `["ctrl", "q"]`,
+/// a single key name is a chord of one key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord(Vec<Key>);
+
+impl Chord {
+    pub fn new(keys: Vec<Key>) -> Result<Self, &'static str> {
+        let mut unique = Vec::with_capacity(keys.len());
+        for key in keys {
+            if !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+
+        if unique.is_empty() {
+            Err("empty chord")
+        } else {
+            Ok(Self(unique))
+        }
+    }
+
+    pub fn keys(&self) -> &[Key] {
+        &self.0
+    }
+
+    /// All keys of the chord are held down.
+    pub fn is_down(&self, raw_input: &RawInput) -> bool {
+        self.0.iter().all(|key| raw_input.key(*key))
+    }
+}
+
+impl From<Key> for Chord {
+    fn from(key: Key) -> Self {
+        Self(vec![key])
+    }
+}
+
+impl FromStr for Chord {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split('+')
+            .map(|key| key.trim().parse())
+            .collect::<Result<Vec<Key>, _>>()?;
+        Self::new(keys)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, key) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "+")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Chord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+struct ChordVisitor;
+
+impl<'de> Visitor<'de> for ChordVisitor {
+    type Value = Chord;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a key, keys joined by `+` or a list of keys")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        s.parse()
+            .map_err(|err| de::Error::custom(format!("{} `{}`", err, s)))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut keys = Vec::new();
+        while let Some(key) = seq.next_element()? {
+            keys.push(key);
+        }
+        Chord::new(keys).map_err(de::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Chord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ChordVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(chord: &Chord) -> Vec<String> {
+        chord.keys().iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let chord = serde_json::from_str::<Chord>(r#""Shift + e""#).unwrap();
+        assert_eq!(keys(&chord), vec!["shift", "e"]);
+        assert_eq!(chord.to_string(), "shift+e");
+
+        let chord = serde_json::from_str::<Chord>(r#"["ctrl", "q", "ctrl"]"#).unwrap();
+        assert_eq!(keys(&chord), vec!["ctrl", "q"]);
+
+        let chord = serde_json::from_str::<Chord>(r#""space""#).unwrap();
+        assert_eq!(chord, Chord::from("space".parse::<Key>().unwrap()));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(serde_json::from_str::<Chord>(r#""shift+""#).is_err());
+        assert!(serde_json::from_str::<Chord>(r#""shift+trinagle""#).is_err());
+        assert!(serde_json::from_str::<Chord>(r#"[]"#).is_err());
+        assert!(serde_json::from_str::<Chord>(r#"["ctrl", 5]"#).is_err());
+    }
+
+    #[test]
+    fn all_keys_down() {
+        let chord = "ctrl+q".parse::<Chord>().unwrap();
+        let mut raw_input = RawInput::mock();
+
+        raw_input.set_key("q".parse().unwrap(), true);
+        assert!(!chord.is_down(&raw_input));
+
+        raw_input.set_key("ctrl".parse().unwrap(), true);
+        assert!(chord.is_down(&raw_input));
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A virtual-key code as it is reported by the raw input api.
+/// Codes above 0xFF are virtual inputs without a key of their own, like the mouse wheel.
+///
+/// Keys are parsed from their canonical name (e.g. `"escape"`), one of their aliases
+/// (e.g. `"esc"`) or from their raw code in the form `"vk_1b"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(u16);
+
+/// Number of key codes, virtual-key codes and virtual inputs.
+pub const KEY_COUNT: usize = 0x104;
+
+// (code, canonical name, aliases)
+const KEYS: &[(u16, &str, &[&str])] = &[
+    (0x01, "mouse1", &["lbutton"]),
+    (0x02, "mouse2", &["rbutton"]),
+    (0x03, "cancel", &[]),
+    (0x04, "mouse3", &["mbutton"]),
+    (0x05, "mouse4", &["xbutton1"]),
+    (0x06, "mouse5", &["xbutton2"]),
+    (0x08, "backspace", &["back"]),
+    (0x09, "tab", &[]),
+    (0x0C, "clear", &[]),
+    (0x0D, "enter", &["return"]),
+    (0x10, "shift", &[]),
+    (0x11, "ctrl", &["control"]),
+    (0x12, "alt", &["menu"]),
+    (0x13, "pause", &[]),
+    (0x14, "capslock", &["caps", "capital"]),
+    (0x15, "kana", &["hangul"]),
+    (0x16, "ime_on", &[]),
+    (0x17, "junja", &[]),
+    (0x18, "final", &[]),
+    (0x19, "kanji", &["hanja"]),
+    (0x1A, "ime_off", &[]),
+    (0x1B, "escape", &["esc"]),
+    (0x1C, "convert", &[]),
+    (0x1D, "nonconvert", &[]),
+    (0x1E, "accept", &[]),
+    (0x1F, "modechange", &[]),
+    (0x20, "space", &[]),
+    (0x21, "pgup", &["pageup", "prior"]),
+    (0x22, "pgdn", &["pagedown", "next"]),
+    (0x23, "end", &[]),
+    (0x24, "home", &[]),
+    (0x25, "left", &[]),
+    (0x26, "up", &[]),
+    (0x27, "right", &[]),
+    (0x28, "down", &[]),
+    (0x29, "select", &[]),
+    (0x2A, "print", &[]),
+    (0x2B, "execute", &[]),
+    (0x2C, "printscreen", &["prtsc", "snapshot"]),
+    (0x2D, "insert", &["ins"]),
+    (0x2E, "delete", &["del"]),
+    (0x2F, "help", &[]),
+    (0x30, "0", &[]),
+    (0x31, "1", &[]),
+    (0x32, "2", &[]),
+    (0x33, "3", &[]),
+    (0x34, "4", &[]),
+    (0x35, "5", &[]),
+    (0x36, "6", &[]),
+    (0x37, "7", &[]),
+    (0x38, "8", &[]),
+    (0x39, "9", &[]),
+    (0x41, "a", &[]),
+    (0x42, "b", &[]),
+    (0x43, "c", &[]),
+    (0x44, "d", &[]),
+    (0x45, "e", &[]),
+    (0x46, "f", &[]),
+    (0x47, "g", &[]),
+    (0x48, "h", &[]),
+    (0x49, "i", &[]),
+    (0x4A, "j", &[]),
+    (0x4B, "k", &[]),
+    (0x4C, "l", &[]),
+    (0x4D, "m", &[]),
+    (0x4E, "n", &[]),
+    (0x4F, "o", &[]),
+    (0x50, "p", &[]),
+    (0x51, "q", &[]),
+    (0x52, "r", &[]),
+    (0x53, "s", &[]),
+    (0x54, "t", &[]),
+    (0x55, "u", &[]),
+    (0x56, "v", &[]),
+    (0x57, "w", &[]),
+    (0x58, "x", &[]),
+    (0x59, "y", &[]),
+    (0x5A, "z", &[]),
+    (0x5B, "lwin", &["win"]),
+    (0x5C, "rwin", &[]),
+    (0x5D, "apps", &["context_menu"]),
+    (0x5F, "sleep", &[]),
+    (0x60, "kp_0", &["numpad0"]),
+    (0x61, "kp_1", &["numpad1"]),
+    (0x62, "kp_2", &["numpad2"]),
+    (0x63, "kp_3", &["numpad3"]),
+    (0x64, "kp_4", &["numpad4"]),
+    (0x65, "kp_5", &["numpad5"]),
+    (0x66, "kp_6", &["numpad6"]),
+    (0x67, "kp_7", &["numpad7"]),
+    (0x68, "kp_8", &["numpad8"]),
+    (0x69, "kp_9", &["numpad9"]),
+    (0x6A, "kp_multiply", &["kp_mul", "multiply"]),
+    (0x6B, "kp_add", &["kp_plus", "add"]),
+    (0x6C, "kp_separator", &["separator"]),
+    (0x6D, "kp_subtract", &["kp_minus", "subtract"]),
+    (0x6E, "kp_decimal", &["kp_period", "decimal"]),
+    (0x6F, "kp_divide", &["kp_div", "divide"]),
+    (0x70, "f1", &[]),
+    (0x71, "f2", &[]),
+    (0x72, "f3", &[]),
+    (0x73, "f4", &[]),
+    (0x74, "f5", &[]),
+    (0x75, "f6", &[]),
+    (0x76, "f7", &[]),
+    (0x77, "f8", &[]),
+    (0x78, "f9", &[]),
+    (0x79, "f10", &[]),
+    (0x7A, "f11", &[]),
+    (0x7B, "f12", &[]),
+    (0x7C, "f13", &[]),
+    (0x7D, "f14", &[]),
+    (0x7E, "f15", &[]),
+    (0x7F, "f16", &[]),
+    (0x80, "f17", &[]),
+    (0x81, "f18", &[]),
+    (0x82, "f19", &[]),
+    (0x83, "f20", &[]),
+    (0x84, "f21", &[]),
+    (0x85, "f22", &[]),
+    (0x86, "f23", &[]),
+    (0x87, "f24", &[]),
+    (0x90, "numlock", &[]),
+    (0x91, "scrolllock", &["scroll"]),
+    (0xA0, "lshift", &[]),
+    (0xA1, "rshift", &[]),
+    (0xA2, "lctrl", &["lcontrol"]),
+    (0xA3, "rctrl", &["rcontrol"]),
+    (0xA4, "lalt", &["lmenu"]),
+    (0xA5, "ralt", &["rmenu", "altgr"]),
+    (0xA6, "browser_back", &[]),
+    (0xA7, "browser_forward", &[]),
+    (0xA8, "browser_refresh", &[]),
+    (0xA9, "browser_stop", &[]),
+    (0xAA, "browser_search", &[]),
+    (0xAB, "browser_favorites", &[]),
+    (0xAC, "browser_home", &[]),
+    (0xAD, "volume_mute", &["mute"]),
+    (0xAE, "volume_down", &[]),
+    (0xAF, "volume_up", &[]),
+    (0xB0, "media_next", &["next_track"]),
+    (0xB1, "media_prev", &["prev_track"]),
+    (0xB2, "media_stop", &[]),
+    (0xB3, "media_play_pause", &["play_pause"]),
+    (0xB4, "launch_mail", &["mail"]),
+    (0xB5, "launch_media_select", &["media_select"]),
+    (0xB6, "launch_app1", &[]),
+    (0xB7, "launch_app2", &[]),
+    (0xBA, "semicolon", &[";", "oem_1"]),
+    (0xBB, "equals", &["=", "oem_plus"]),
+    (0xBC, "comma", &[",", "oem_comma"]),
+    (0xBD, "minus", &["-", "oem_minus"]),
+    (0xBE, "period", &[".", "oem_period"]),
+    (0xBF, "slash", &["/", "oem_2"]),
+    (0xC0, "grave", &["`", "tilde", "oem_3"]),
+    (0xDB, "lbracket", &["[", "oem_4"]),
+    (0xDC, "backslash", &["\\", "oem_5"]),
+    (0xDD, "rbracket", &["]", "oem_6"]),
+    (0xDE, "apostrophe", &["'", "quote", "oem_7"]),
+    (0xDF, "oem_8", &[]),
+    (0xE2, "oem_102", &["intl_backslash"]),
+    (0xE5, "processkey", &[]),
+    (0xE7, "packet", &[]),
+    (0xF6, "attn", &[]),
+    (0xF7, "crsel", &[]),
+    (0xF8, "exsel", &[]),
+    (0xF9, "ereof", &[]),
+    (0xFA, "play", &[]),
+    (0xFB, "zoom", &[]),
+    (0xFC, "noname", &[]),
+    (0xFD, "pa1", &[]),
+    (0xFE, "oem_clear", &[]),
+    // one pulse per wheel notch
+    (0x100, "wheel_up", &[]),
+    (0x101, "wheel_down", &[]),
+    (0x102, "wheel_left", &[]),
+    (0x103, "wheel_right", &[]),
+];
+
+impl Key {
+    pub const WHEEL_UP: Key = Key(0x100);
+    pub const WHEEL_DOWN: Key = Key(0x101);
+    pub const WHEEL_LEFT: Key = Key(0x102);
+    pub const WHEEL_RIGHT: Key = Key(0x103);
+
+    /// Returns the key for the given code.
+    /// 0x00 and 0xFF are not valid virtual-key codes.
+    pub fn from_code(code: u16) -> Option<Self> {
+        match code {
+            0x00 | 0xFF => None,
+            code if code as usize >= KEY_COUNT => None,
+            code => Some(Self(code)),
+        }
+    }
+
+    pub fn code(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the canonical name of the key if it has one.
+    /// Unassigned and oem specific codes are only reachable via their `vk_xx` form.
+    pub fn name(self) -> Option<&'static str> {
+        KEYS.iter()
+            .find(|(code, _, _)| *code == self.0)
+            .map(|(_, name, _)| *name)
+    }
+}
+
+impl FromStr for Key {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+
+        if let Some((code, _, _)) = KEYS
+            .iter()
+            .find(|(_, canonical, aliases)| *canonical == name || aliases.contains(&name.as_str()))
+        {
+            return Ok(Self(*code));
+        }
+
+        if let Some(code) = name.strip_prefix("vk_") {
+            let code = u8::from_str_radix(code.trim_start_matches("0x"), 16)
+                .map_err(|_| "invalid virtual-key code")?;
+            return Self::from_code(code as u16).ok_or("invalid virtual-key code");
+        }
+
+        Err("unknown key")
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "vk_{:02x}", self.0),
+        }
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|err| de::Error::custom(format!("{} `{}`", err, name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_name_round_trip() {
+        for code in (0x01..=0xFE).chain(0x100..KEY_COUNT as u16) {
+            let key = Key::from_code(code).unwrap();
+            assert_eq!(
+                key.to_string().parse::<Key>(),
+                Ok(key),
+                "code 0x{:02x}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn aliases_resolve_to_code() {
+        for (code, name, aliases) in KEYS {
+            assert_eq!(name.parse::<Key>().unwrap().code(), *code, "{}", name);
+            for alias in aliases.iter() {
+                assert_eq!(alias.parse::<Key>().unwrap().code(), *code, "{}", alias);
+            }
+        }
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let mut names = KEYS
+            .iter()
+            .flat_map(|(_, name, aliases)| std::iter::once(name).chain(aliases.iter()))
+            .collect::<Vec<_>>();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
+    }
+
+    #[test]
+    fn digits() {
+        assert_eq!("0".parse::<Key>().unwrap().code(), 0x30);
+        assert_eq!("9".parse::<Key>().unwrap().code(), 0x39);
+    }
+
+    #[test]
+    fn wheel() {
+        assert_eq!("wheel_up".parse::<Key>(), Ok(Key::WHEEL_UP));
+        assert_eq!(Key::WHEEL_RIGHT.to_string(), "wheel_right");
+        assert!(Key::from_code(KEY_COUNT as u16).is_none());
+        assert!("vk_100".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn raw_codes() {
+        assert_eq!("vk_92".parse::<Key>().unwrap().code(), 0x92);
+        assert_eq!("VK_0x1B".parse::<Key>().unwrap().name(), Some("escape"));
+        assert!("vk_00".parse::<Key>().is_err());
+        assert!("vk_ff".parse::<Key>().is_err());
+        assert!("vk_zz".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn unknown_key() {
+        assert!("trinagle".parse::<Key>().is_err());
+        assert!(serde_json::from_str::<Key>("\"trinagle\"").is_err());
+        assert_eq!(serde_json::from_str::<Key>("\"ESC\"").unwrap().code(), 0x1B);
+    }
+}
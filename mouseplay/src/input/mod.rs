@@ -1 +1,3 @@
-pub mod raw_input;
+pub mod chord;
+pub mod key;
+pub mod raw_input;
@@ -1,11 +1,19 @@
+#[cfg(windows)]
 use std::collections::HashMap;
+#[cfg(windows)]
 use std::ffi::{c_void, CString};
+#[cfg(windows)]
 use std::mem::size_of;
+#[cfg(windows)]
 use std::sync::RwLock;
+use std::time::{Duration, Instant};
 
+#[cfg(windows)]
 use log::{info, trace, warn};
 
+#[cfg(windows)]
 use lazy_static::lazy_static;
+#[cfg(windows)]
 use winapi::{
     shared::{
         minwindef::{LOWORD, LPARAM, LRESULT, UINT, WPARAM},
@@ -15,6 +23,9 @@ use winapi::{
     um::winuser::*,
 };
 
+use super::key::{Key, KEY_COUNT};
+
+#[cfg(windows)]
 lazy_static! {
     // thread safe storage for all known wndprocs
     static ref ORIG_WNDPROCS: RwLock<HashMap<u64, u64>> = RwLock::new(HashMap::new());
@@ -23,6 +34,7 @@ lazy_static! {
     pub static ref RAW_INPUT: RwLock<RawInput> = RwLock::new(RawInput::new().unwrap());
 }
 
+#[cfg(windows)]
 pub fn hijack_wndproc() -> Result<(), &'static str> {
     let window_name =
         CString::new("PS Remote Play").map_err(|_| "unable to convert window_name")?;
@@ -50,6 +62,7 @@ pub fn hijack_wndproc() -> Result<(), &'static str> {
     Ok(())
 }
 
+#[cfg(windows)]
 unsafe extern "system" fn hook_wndproc(
     h_wnd: HWND,
     u_msg: UINT,
@@ -81,24 +94,157 @@ unsafe extern "system" fn hook_wndproc(
     0
 }
 
+#[cfg(windows)]
 const LBUTTONDOWN: u16 = 1 << 0;
+#[cfg(windows)]
 const LBUTTONUP: u16 = 1 << 1;
+#[cfg(windows)]
 const RBUTTONDOWN: u16 = 1 << 2;
+#[cfg(windows)]
 const RBUTTONUP: u16 = 1 << 3;
+#[cfg(windows)]
 const MBUTTONDOWN: u16 = 1 << 4;
+#[cfg(windows)]
 const MBUTTONUP: u16 = 1 << 5;
+#[cfg(windows)]
 const XBUTTON1DOWN: u16 = 1 << 6;
+#[cfg(windows)]
 const XBUTTON1UP: u16 = 1 << 7;
+#[cfg(windows)]
 const XBUTTON2DOWN: u16 = 1 << 8;
+#[cfg(windows)]
 const XBUTTON2UP: u16 = 1 << 9;
+#[cfg(windows)]
+const WHEEL: u16 = 1 << 10;
+#[cfg(windows)]
+const HWHEEL: u16 = 1 << 11;
+
+// one wheel notch, high resolution wheels report fractions of it
+const WHEEL_DELTA: i32 = 120;
+
+// notches queued per wheel direction, a fast spin is not played back long after it stopped
+const MAX_PENDING_NOTCHES: u32 = 2;
+
+// the wheel directions in the order of `RawInput::wheel`
+const WHEEL_KEYS: [Key; 4] = [
+    Key::WHEEL_UP,
+    Key::WHEEL_DOWN,
+    Key::WHEEL_LEFT,
+    Key::WHEEL_RIGHT,
+];
+
+/// Turns the notches of one wheel direction into key presses of equal length.
+#[derive(Default)]
+struct WheelPulse {
+    pending: u32,
+    pressed_at: Option<Instant>,
+    released_at: Option<Instant>,
+}
+
 pub struct RawInput {
+    #[cfg_attr(not(windows), allow(dead_code))]
     capture: Option<u64>,
     mouse_lock: bool,
-    keys: [bool; 256],
+    keys: [bool; KEY_COUNT],
     mouse: [i32; 2],
+    #[cfg_attr(not(windows), allow(dead_code))]
     mouse_accumulator: [i32; 2],
+    // vertical and horizontal delta that did not make up a full notch yet
+    wheel_accumulator: [i32; 2],
+    wheel: [WheelPulse; 4],
+}
+
+impl RawInput {
+    pub fn key(&self, key: Key) -> bool {
+        self.keys[key.code() as usize]
+    }
+
+    /// True while the mouse is captured by the remote play window.
+    pub fn mouse_lock(&self) -> bool {
+        self.mouse_lock
+    }
+
+    // TODO:
+    pub fn mouse_x(&self) -> i32 {
+        self.mouse[0]
+    }
+
+    pub fn mouse_y(&self) -> i32 {
+        self.mouse[1]
+    }
+
+    // positive vertical deltas scroll up, positive horizontal ones to the right
+    fn scroll(&mut self, horizontal: bool, delta: i32) {
+        // accumulator and the indices of the negative and positive direction in `wheel`
+        let (axis, negative, positive) = if horizontal { (1, 2, 3) } else { (0, 1, 0) };
+        self.wheel_accumulator[axis] += delta;
+        while self.wheel_accumulator[axis] >= WHEEL_DELTA {
+            self.wheel_accumulator[axis] -= WHEEL_DELTA;
+            self.wheel[positive].pending =
+                (self.wheel[positive].pending + 1).min(MAX_PENDING_NOTCHES);
+        }
+        while self.wheel_accumulator[axis] <= -WHEEL_DELTA {
+            self.wheel_accumulator[axis] += WHEEL_DELTA;
+            self.wheel[negative].pending =
+                (self.wheel[negative].pending + 1).min(MAX_PENDING_NOTCHES);
+        }
+    }
+
+    /// Presses and releases the wheel keys, every notch is held for `pulse`
+    /// and followed by a release just as long so quick notches stay apart.
+    pub fn update_wheel(&mut self, pulse: Duration, now: Instant) {
+        for (wheel, key) in self.wheel.iter_mut().zip(WHEEL_KEYS.iter()) {
+            match wheel.pressed_at {
+                Some(pressed_at) if now.duration_since(pressed_at) >= pulse => {
+                    wheel.pressed_at = None;
+                    wheel.released_at = Some(now);
+                }
+                Some(_) => {}
+                None => {
+                    let idle = match wheel.released_at {
+                        Some(released_at) => now.duration_since(released_at) >= pulse,
+                        None => true,
+                    };
+                    if wheel.pending > 0 && idle {
+                        wheel.pending -= 1;
+                        wheel.pressed_at = Some(now);
+                    }
+                }
+            }
+            self.keys[key.code() as usize] = wheel.pressed_at.is_some();
+        }
+    }
+}
+
+#[cfg(test)]
+impl RawInput {
+    /// Creates an input state that is not connected to any window.
+    pub fn mock() -> Self {
+        Self {
+            capture: None,
+            mouse_lock: true,
+            keys: [false; KEY_COUNT],
+            mouse: [0; 2],
+            mouse_accumulator: [0; 2],
+            wheel_accumulator: [0; 2],
+            wheel: Default::default(),
+        }
+    }
+
+    pub fn set_key(&mut self, key: Key, down: bool) {
+        self.keys[key.code() as usize] = down;
+    }
+
+    pub fn set_mouse(&mut self, x: i32, y: i32) {
+        self.mouse = [x, y];
+    }
+
+    pub fn set_mouse_lock(&mut self, mouse_lock: bool) {
+        self.mouse_lock = mouse_lock;
+    }
 }
 
+#[cfg(windows)]
 impl RawInput {
     pub fn new() -> Result<Self, &'static str> {
         let rid = [
@@ -123,31 +269,17 @@ impl RawInput {
             Ok(Self {
                 capture: None,
                 mouse_lock: false,
-                keys: [false; 256],
+                keys: [false; KEY_COUNT],
                 mouse: [0; 2],
                 mouse_accumulator: [0; 2],
+                wheel_accumulator: [0; 2],
+                wheel: Default::default(),
             })
         } else {
             Err("unable to register raw input devices")
         }
     }
 
-    pub fn key(&self, button: &str) -> bool {
-        match str_to_vk(button) {
-            Ok(vk) => self.keys[vk as usize],
-            Err(_) => false,
-        }
-    }
-
-    // TODO:
-    pub fn mouse_x(&self) -> i32 {
-        self.mouse[0]
-    }
-
-    pub fn mouse_y(&self) -> i32 {
-        self.mouse[1]
-    }
-
     pub fn parse(
         &mut self,
         h_wnd: HWND,
@@ -284,6 +416,13 @@ impl RawInput {
         if mouse.usButtonFlags & XBUTTON2UP != 0 {
             self.keys[VK_XBUTTON2 as usize] = false;
         }
+        // the wheel delta is signed but stored in an unsigned field
+        if mouse.usButtonFlags & WHEEL != 0 {
+            self.scroll(false, mouse.usButtonData as i16 as i32);
+        }
+        if mouse.usButtonFlags & HWHEEL != 0 {
+            self.scroll(true, mouse.usButtonData as i16 as i32);
+        }
     }
 
     fn parse_keyboard(&mut self, kbd: &RAWKEYBOARD) {
@@ -339,98 +478,58 @@ impl RawInput {
     }
 }
 
-fn str_to_vk(key: &str) -> Result<i32, &'static str> {
-    match key {
-        "mouse1" => Ok(VK_LBUTTON),
-        "mouse2" => Ok(VK_RBUTTON),
-        "mouse3" => Ok(VK_MBUTTON),
-        "mouse4" => Ok(VK_XBUTTON1),
-        "mouse5" => Ok(VK_XBUTTON2),
-        "shift" => Ok(VK_SHIFT),
-        "lshift" => Ok(VK_LSHIFT),
-        "rshift" => Ok(VK_RSHIFT),
-        "alt" => Ok(VK_MENU),
-        "lalt" => Ok(VK_LMENU),
-        "ralt" => Ok(VK_RMENU),
-        "ctrl" => Ok(VK_CONTROL),
-        "lctrl" => Ok(VK_LCONTROL),
-        "rctrl" => Ok(VK_RCONTROL),
-        "tab" => Ok(VK_TAB),
-        "up" => Ok(VK_UP),
-        "down" => Ok(VK_DOWN),
-        "left" => Ok(VK_LEFT),
-        "right" => Ok(VK_RIGHT),
-        "insert" => Ok(VK_INSERT),
-        "delete" => Ok(VK_DELETE),
-        "home" => Ok(VK_HOME),
-        "end" => Ok(VK_END),
-        "pgup" => Ok(VK_PRIOR),
-        "pgdn" => Ok(VK_NEXT),
-        "backspace" => Ok(VK_BACK),
-        "enter" => Ok(VK_RETURN),
-        "pause" => Ok(VK_PAUSE),
-        "numlock" => Ok(VK_NUMLOCK),
-        "space" => Ok(VK_SPACE),
-        "kp_0" => Ok(VK_NUMPAD0),
-        "kp_1" => Ok(VK_NUMPAD1),
-        "kp_2" => Ok(VK_NUMPAD2),
-        "kp_3" => Ok(VK_NUMPAD3),
-        "kp_4" => Ok(VK_NUMPAD4),
-        "kp_5" => Ok(VK_NUMPAD5),
-        "kp_6" => Ok(VK_NUMPAD6),
-        "kp_7" => Ok(VK_NUMPAD7),
-        "kp_8" => Ok(VK_NUMPAD8),
-        "kp_9" => Ok(VK_NUMPAD9),
-        "esc" => Ok(VK_ESCAPE),
-        "escape" => Ok(VK_ESCAPE),
-        "f1" => Ok(VK_F1),
-        "f2" => Ok(VK_F2),
-        "f3" => Ok(VK_F3),
-        "f4" => Ok(VK_F4),
-        "f5" => Ok(VK_F5),
-        "f6" => Ok(VK_F6),
-        "f7" => Ok(VK_F7),
-        "f8" => Ok(VK_F8),
-        "f9" => Ok(VK_F9),
-        "f10" => Ok(VK_F10),
-        "f11" => Ok(VK_F11),
-        "f12" => Ok(VK_F12),
-        "0" => Ok(58),
-        "1" => Ok(49),
-        "2" => Ok(50),
-        "3" => Ok(51),
-        "4" => Ok(52),
-        "5" => Ok(53),
-        "6" => Ok(54),
-        "7" => Ok(55),
-        "8" => Ok(56),
-        "9" => Ok(57),
-        "a" => Ok(65),
-        "b" => Ok(66),
-        "c" => Ok(67),
-        "d" => Ok(68),
-        "e" => Ok(69),
-        "f" => Ok(70),
-        "g" => Ok(71),
-        "h" => Ok(72),
-        "i" => Ok(73),
-        "j" => Ok(74),
-        "k" => Ok(75),
-        "l" => Ok(76),
-        "m" => Ok(77),
-        "n" => Ok(78),
-        "o" => Ok(79),
-        "p" => Ok(80),
-        "q" => Ok(81),
-        "r" => Ok(82),
-        "s" => Ok(83),
-        "t" => Ok(84),
-        "u" => Ok(85),
-        "v" => Ok(86),
-        "w" => Ok(87),
-        "x" => Ok(88),
-        "y" => Ok(89),
-        "z" => Ok(90),
-        _ => Err("invalid keycode"),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wheel_pulses() {
+        let mut raw_input = RawInput::mock();
+        let pulse = Duration::from_millis(50);
+        let start = Instant::now();
+        let wheel_up = |raw_input: &mut RawInput, ms: u64| {
+            raw_input.update_wheel(pulse, start + Duration::from_millis(ms));
+            raw_input.key(Key::WHEEL_UP)
+        };
+
+        // two notches at once, half a notch stays behind
+        raw_input.scroll(false, 2 * WHEEL_DELTA + WHEEL_DELTA / 2);
+        assert!(wheel_up(&mut raw_input, 0));
+        assert!(wheel_up(&mut raw_input, 40));
+        assert!(!wheel_up(&mut raw_input, 50));
+        assert!(!wheel_up(&mut raw_input, 90));
+        assert!(wheel_up(&mut raw_input, 100));
+        assert!(!wheel_up(&mut raw_input, 150));
+        assert!(!wheel_up(&mut raw_input, 300));
+
+        raw_input.scroll(false, WHEEL_DELTA / 2);
+        assert!(wheel_up(&mut raw_input, 310));
+
+        raw_input.scroll(true, -WHEEL_DELTA);
+        raw_input.update_wheel(pulse, start + Duration::from_millis(320));
+        assert!(raw_input.key(Key::WHEEL_LEFT));
+        assert!(!raw_input.key(Key::WHEEL_DOWN));
+        assert!(!raw_input.key(Key::WHEEL_RIGHT));
+    }
+
+    #[test]
+    fn wheel_spin_is_capped() {
+        let mut raw_input = RawInput::mock();
+        let pulse = Duration::from_millis(50);
+        let start = Instant::now();
+
+        // ten notches in one report only queue as many taps as the cap
+        raw_input.scroll(false, -10 * WHEEL_DELTA);
+        let mut taps = 0;
+        let mut was_down = false;
+        for ms in (0..2000).step_by(10) {
+            raw_input.update_wheel(pulse, start + Duration::from_millis(ms));
+            let down = raw_input.key(Key::WHEEL_DOWN);
+            if down && !was_down {
+                taps += 1;
+            }
+            was_down = down;
+        }
+        assert_eq!(taps, MAX_PENDING_NOTCHES);
     }
 }
@@ -1,11 +1,21 @@
+// everything is reached through the hooks, other platforms only build it for the tests
+#[cfg(windows)]
 mod console;
+#[cfg(any(windows, test))]
 mod controller;
+#[cfg(any(windows, test))]
+mod feedback;
+#[cfg(windows)]
 mod hooks;
+#[cfg(any(windows, test))]
 mod input;
+#[cfg(any(windows, test))]
 mod mapper;
 
+#[cfg(windows)]
 use winapi::um::winnt::{DLL_PROCESS_ATTACH, DLL_PROCESS_DETACH};
 
+#[cfg(windows)]
 #[no_mangle]
 extern "system" fn DllMain(_hinst: *const u8, reason: u32, _reserved: *const u8) -> u32 {
     match reason {
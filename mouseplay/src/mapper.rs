@@ -1,18 +1,32 @@
-use std::ffi::CStr;
-use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::path::Path;
+#[cfg(windows)]
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::RwLock;
+use std::time::{Duration, Instant};
 
-use log::info;
+use log::{error, info};
 
+#[cfg(windows)]
+use crate::controller::virtual_ds4::VIRTUAL_DS4;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use winapi::{
-    shared::minwindef::MAX_PATH,
-    um::{libloaderapi::GetModuleFileNameA, winnt::IMAGE_DOS_HEADER},
-};
+#[cfg(windows)]
+use winapi::{shared::minwindef::MAX_PATH, um::libloaderapi::GetModuleFileNameA};
 
-use crate::{controller::ds4::DS4, input::raw_input::RawInput};
+use crate::{
+    controller::{
+        ds4::{TOUCHPAD_HEIGHT, TOUCHPAD_WIDTH},
+        ds4_feature::DeviceIdentity,
+        ds4_output::OutputState,
+        Axis, Button, Controller, TouchPoint,
+    },
+    feedback::{Feedback, FeedbackConfig, FeedbackOverride},
+    input::{chord::Chord, key::Key, raw_input::RawInput},
+};
 
+#[cfg(windows)]
 extern "C" {
     pub static __ImageBase: u8;
 }
@@ -22,13 +36,23 @@ lazy_static! {
     pub static ref MAPPER: RwLock<Option<Mapper>> = RwLock::new(None);
 }
 
+#[cfg(windows)]
 pub fn load(file_name: &str) -> Result<(), &'static str> {
     let library_dir = get_library_dir()?;
+    let mapper = Mapper::load(library_dir.join(file_name))?;
+    if let Some(identity) = mapper.virtual_controller() {
+        VIRTUAL_DS4
+            .write()
+            .map_err(|_| "unable to lock virtual controller")?
+            .set_identity(identity.clone());
+    }
+
     let mut lock = MAPPER.write().map_err(|_| "unable to lock mapper")?;
-    *lock = Some(Mapper::load(library_dir.join(file_name))?);
+    *lock = Some(mapper);
     Ok(())
 }
 
+#[cfg(windows)]
 fn get_library_dir() -> Result<PathBuf, &'static str> {
     let mut buffer = vec![0u8; MAX_PATH];
     unsafe {
@@ -49,45 +73,423 @@ fn get_library_dir() -> Result<PathBuf, &'static str> {
     Ok(file_path.to_path_buf())
 }
 
+/// How the held state of an input turns into the state of the output.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputMode {
+    /// The output is active while the input is held.
+    #[default]
+    Hold,
+    /// Pressing the input switches the output on or off.
+    Toggle,
+    /// Releasing the input switches the output on or off.
+    ToggleOnRelease,
+}
+
+/// Remembers the state of an input across frames for the toggle modes.
+#[derive(Debug, Default)]
+struct Latch {
+    latched: bool,
+    was_down: bool,
+}
+
+impl Latch {
+    // the state `update` is going to return, without changing the latch
+    fn peek(&self, mode: InputMode, down: bool) -> bool {
+        let pressed = down && !self.was_down;
+        let released = !down && self.was_down;
+
+        match mode {
+            InputMode::Hold => down,
+            InputMode::Toggle => self.latched != pressed,
+            InputMode::ToggleOnRelease => self.latched != released,
+        }
+    }
+
+    fn update(&mut self, mode: InputMode, down: bool) -> bool {
+        let active = self.peek(mode, down);
+        self.was_down = down;
+        if mode != InputMode::Hold {
+            self.latched = active;
+        }
+        active
+    }
+
+    fn reset(&mut self) {
+        self.latched = false;
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ButtonMapping {
-    input: String,
-    output: String,
+    input: Chord,
+    output: Button,
+    /// While the chord is held, mappings on a part of it are suppressed.
+    #[serde(default)]
+    exclusive: bool,
+    #[serde(default)]
+    mode: InputMode,
+    #[serde(default)]
+    turbo: Option<Turbo>,
+
+    // internal state
+    #[serde(skip)]
+    latch: Latch,
+    #[serde(skip)]
+    active_since: Option<Instant>,
 }
 
 impl ButtonMapping {
-    fn map_controller(&mut self, raw_input: &RawInput, ds4: &mut DS4) {
-        let down = raw_input.key(&self.input);
+    fn validate(&self) -> Result<(), &'static str> {
+        match &self.turbo {
+            Some(turbo) => turbo.validate(),
+            None => Ok(()),
+        }
+    }
+
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+    ) {
+        let mut down = self.latch.update(self.mode, self.input.is_down(raw_input));
+
+        // turbo starts with a press and runs on wall clock time, not on the report rate
+        if !down {
+            self.active_since = None;
+        } else if let Some(turbo) = &self.turbo {
+            let active_since = *self.active_since.get_or_insert(now);
+            down = turbo.is_pressed(now.duration_since(active_since));
+        }
+
         if down {
-            ds4.set_btn(&self.output, down);
+            controller.set_btn(self.output, down);
+        }
+    }
+}
+
+fn default_duty_cycle() -> f64 {
+    0.5
+}
+
+/// Presses and releases the output `rate` times per second while the input is active,
+/// `duty_cycle` is the part of each period the output is pressed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Turbo {
+    rate: f64,
+    #[serde(default = "default_duty_cycle")]
+    duty_cycle: f64,
+}
+
+impl Turbo {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.rate <= 0f64 {
+            return Err("turbo rate has to be above 0");
+        }
+        if self.duty_cycle <= 0f64 || self.duty_cycle >= 1f64 {
+            return Err("turbo duty_cycle has to be between 0 and 1");
+        }
+        Ok(())
+    }
+
+    fn is_pressed(&self, active_for: Duration) -> bool {
+        (active_for.as_secs_f64() * self.rate).fract() < self.duty_cycle
+    }
+}
+
+/// How an axis moves towards its target.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RampCurve {
+    /// At a constant speed, the full range takes `time`.
+    #[default]
+    Linear,
+    /// Fast at first and slower when getting close, about two thirds of the way take `time`.
+    Exponential,
+}
+
+/// Moves an axis gradually instead of snapping it to its target.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Ramp {
+    /// Milliseconds, see `RampCurve`.
+    time: u64,
+    #[serde(default)]
+    curve: RampCurve,
+}
+
+impl Ramp {
+    fn step(&self, position: f32, target: f32, elapsed: Duration) -> f32 {
+        if self.time == 0 {
+            return target;
+        }
+        let progress = elapsed.as_secs_f32() * 1000f32 / self.time as f32;
+        match self.curve {
+            RampCurve::Linear => {
+                let step = 255f32 * progress;
+                if (target - position).abs() <= step {
+                    target
+                } else {
+                    position + step.copysign(target - position)
+                }
+            }
+            RampCurve::Exponential => {
+                let position = target + (position - target) * (-progress).exp();
+                // the curve never quite gets there
+                if (target - position).abs() < 0.5f32 {
+                    target
+                } else {
+                    position
+                }
+            }
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AxisMapping {
-    input: String,
-    output: String,
+    input: Chord,
+    output: Axis,
     value: f32,
+    /// While the chord is held, mappings on a part of it are suppressed.
+    #[serde(default)]
+    exclusive: bool,
+    #[serde(default)]
+    mode: InputMode,
+    /// Ramp towards `value` while the input is active.
+    #[serde(default)]
+    attack: Option<Ramp>,
+    /// Ramp back to the rest position of the axis once the input is inactive,
+    /// cut short as soon as another `Axis` mapping on the same output becomes active.
+    #[serde(default)]
+    release: Option<Ramp>,
+
+    // internal state
+    #[serde(skip)]
+    latch: Latch,
+    // where the ramps left the axis, none while it is at rest
+    #[serde(skip)]
+    position: Option<f32>,
+    #[serde(skip)]
+    updated_at: Option<Instant>,
 }
 
 impl AxisMapping {
-    fn map_controller(&mut self, raw_input: &RawInput, ds4: &mut DS4) {
-        let down = raw_input.key(&self.input);
-        if down {
-            let value = (0.5f32 + (self.value.max(-1f32).min(1f32) / 2f32)) * 255f32;
-            //trace!("axis={}, value={}", self.output, value);
-            ds4.set_axis(&self.output, value as u8);
+    fn reset(&mut self) {
+        self.latch.reset();
+        self.position = None;
+        self.updated_at = None;
+    }
+
+    fn is_active(&self, raw_input: &RawInput) -> bool {
+        self.latch.peek(self.mode, self.input.is_down(raw_input))
+    }
+
+    // `claimed` are the axes other active mappings write this frame, a release ramp on one of
+    // them stops instead of fighting over the axis
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+        scale: Scale,
+        claimed: &[Axis],
+    ) {
+        let down = self.latch.update(self.mode, self.input.is_down(raw_input));
+        if !down && claimed.contains(&self.output) {
+            self.position = None;
+        }
+        let elapsed = self
+            .updated_at
+            .map(|updated_at| now.duration_since(updated_at))
+            .unwrap_or_default();
+        self.updated_at = Some(now);
+
+        let rest = self.output.rest() as f32;
+        let target = if down {
+            let mut value = self.value.max(-1f32).min(1f32);
+            if self.output.is_stick() {
+                value *= scale.stick;
+            }
+            (0.5f32 + (value / 2f32)) * 255f32
+        } else {
+            rest
+        };
+
+        let position = self.position.unwrap_or(rest);
+        let ramp = if down { &self.attack } else { &self.release };
+        let position = match ramp {
+            Some(ramp) => ramp.step(position, target, elapsed),
+            None => target,
+        };
+        self.position = if down || position != rest {
+            Some(position)
+        } else {
+            None
+        };
+
+        if let Some(position) = self.position {
+            //trace!("axis={}, value={}", self.output, position);
+            controller.set_axis(self.output, position as u8);
+        }
+    }
+}
+
+/// What a stick does while both keys of one of its axes are held.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Socd {
+    /// The key pressed last wins.
+    #[default]
+    LastWins,
+    /// The axis goes back to the center.
+    Neutral,
+    /// The key pressed first wins.
+    FirstWins,
+}
+
+/// The keys of one stick axis and which of them went down last.
+#[derive(Debug, Default)]
+struct KeyAxis {
+    negative: bool,
+    positive: bool,
+    last: f32,
+}
+
+impl KeyAxis {
+    fn update(&mut self, negative: bool, positive: bool, socd: Socd) -> f32 {
+        if negative && !self.negative {
+            self.last = -1f32;
+        }
+        if positive && !self.positive {
+            self.last = 1f32;
+        }
+        self.negative = negative;
+        self.positive = positive;
+
+        match (negative, positive) {
+            (true, true) => match socd {
+                Socd::LastWins => self.last,
+                Socd::Neutral => 0f32,
+                Socd::FirstWins => -self.last,
+            },
+            (true, false) => -1f32,
+            (false, true) => 1f32,
+            (false, false) => 0f32,
+        }
+    }
+}
+
+fn default_circular() -> bool {
+    true
+}
+
+/// Drives both axes of a stick from four direction keys.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyStickMapping {
+    up: Chord,
+    down: Chord,
+    left: Chord,
+    right: Chord,
+    output_x: Axis,
+    output_y: Axis,
+    #[serde(default)]
+    socd: Socd,
+    /// Diagonals stay on the unit circle instead of going into the corners.
+    #[serde(default = "default_circular")]
+    circular: bool,
+
+    // internal state
+    #[serde(skip)]
+    x: KeyAxis,
+    #[serde(skip)]
+    y: KeyAxis,
+}
+
+impl KeyStickMapping {
+    fn inputs(&self) -> Vec<Input> {
+        [&self.up, &self.down, &self.left, &self.right]
+            .iter()
+            .flat_map(|chord| Vec::<Input>::from(*chord))
+            .collect()
+    }
+
+    fn reset(&mut self) {
+        self.x = KeyAxis::default();
+        self.y = KeyAxis::default();
+    }
+
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        scale: Scale,
+    ) {
+        let up = self.up.is_down(raw_input);
+        let down = self.down.is_down(raw_input);
+        let left = self.left.is_down(raw_input);
+        let right = self.right.is_down(raw_input);
+
+        let mut x = self.x.update(left, right, self.socd);
+        let mut y = self.y.update(up, down, self.socd);
+        if !(up || down || left || right) {
+            return;
+        }
+
+        if self.circular && x != 0f32 && y != 0f32 {
+            x *= std::f32::consts::FRAC_1_SQRT_2;
+            y *= std::f32::consts::FRAC_1_SQRT_2;
         }
+        x *= scale.stick;
+        y *= scale.stick;
+        // rounded so a centered axis lands on 128 like on the real stick
+        controller.set_axis(self.output_x, ((0.5f32 + x / 2f32) * 255f32).round() as u8);
+        controller.set_axis(self.output_y, ((0.5f32 + y / 2f32) * 255f32).round() as u8);
     }
 }
 
 const FREQUENCY_SCALE: f64 = 2.8125;
 
+/// Shape of the dead zone the game applies to the stick a `MouseMapping` drives.
+/// The output is pushed just outside of it as soon as the mouse moves.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadZoneShape {
+    /// The stick is ignored within a circle (an ellipse for different x and y dead zones),
+    /// the output is moved out along the direction of the motion.
+    Circle,
+    /// The stick is ignored within a square, the output is moved out along the direction
+    /// of the motion up to the edge of the square.
+    Square,
+    /// Each axis is ignored on its own, every moving axis is moved out by its dead zone.
+    Axial,
+    /// Only the dominant axis of the motion is moved out by its dead zone,
+    /// small motion along the other axis stays in the dead zone.
+    Cross,
+}
+
+impl DeadZoneShape {
+    /// Offset of both axes for motion at the given angle, `angle_cos` and `angle_sin`
+    /// are both positive.
+    fn dead_zone(self, dead_zone: [f64; 2], angle_cos: f64, angle_sin: f64) -> [f64; 2] {
+        match self {
+            DeadZoneShape::Circle => [dead_zone[0] * angle_cos, dead_zone[1] * angle_sin],
+            DeadZoneShape::Square => {
+                // distance to the edge of the square along the motion
+                let distance = f64::min(dead_zone[0] / angle_cos, dead_zone[1] / angle_sin);
+                [distance * angle_cos, distance * angle_sin]
+            }
+            DeadZoneShape::Axial => dead_zone,
+            DeadZoneShape::Cross if angle_cos >= angle_sin => [dead_zone[0], 0f64],
+            DeadZoneShape::Cross => [0f64, dead_zone[1]],
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MouseMapping {
-    output_x: String,
-    output_y: String,
+    output_x: Axis,
+    output_y: Axis,
     multiplier_x: f64,
     multiplier_y: f64,
     dead_zone_x: i32,
@@ -95,7 +497,7 @@ pub struct MouseMapping {
 
     sensitivity: f64,
     exponent: f64,
-    shape: String,
+    shape: DeadZoneShape,
 
     // internal state
     #[serde(skip)]
@@ -107,11 +509,17 @@ pub struct MouseMapping {
 }
 
 impl MouseMapping {
-    fn map_controller(&mut self, raw_input: &RawInput, ds4: &mut DS4) {
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        scale: Scale,
+    ) {
         // this is roughly a copy of the implementation of gimx
+        let sensitivity = self.sensitivity * scale.mouse;
         let mut mouse = [
-            raw_input.mouse_x() as f64 * self.sensitivity,
-            raw_input.mouse_y() as f64 * self.sensitivity,
+            raw_input.mouse_x() as f64 * sensitivity,
+            raw_input.mouse_y() as f64 * sensitivity,
         ];
 
         if mouse[0] != 0f64 || mouse[1] != 0f64 {
@@ -137,54 +545,53 @@ impl MouseMapping {
 
         let exponent = self.exponent;
 
-        let mut dead_zone = [
-            f64::copysign(
-                self.dead_zone_x as f64 * axis_scale,
-                self.multiplier_x * mouse[0],
-            ),
-            f64::copysign(
-                self.dead_zone_y as f64 * axis_scale,
-                self.multiplier_y * mouse[1],
-            ),
-        ];
-
         let hypotenuse = f64::hypot(mouse[0], mouse[1]);
         let angle_cos = mouse[0].abs() / hypotenuse;
         let angle_sin = mouse[1].abs() / hypotenuse;
 
-        if mouse[0] != 0f64 && mouse[1] != 0f64 && self.shape == "circle" {
-            dead_zone[0] *= angle_cos;
-            dead_zone[1] *= angle_sin;
-        }
+        let dead_zone = self.shape.dead_zone(
+            [
+                self.dead_zone_x as f64 * axis_scale,
+                self.dead_zone_y as f64 * axis_scale,
+            ],
+            angle_cos,
+            angle_sin,
+        );
+        let dead_zone = [
+            f64::copysign(dead_zone[0], self.multiplier_x * mouse[0]),
+            f64::copysign(dead_zone[1], self.multiplier_y * mouse[1]),
+        ];
 
         let norm = hypotenuse * FREQUENCY_SCALE;
 
         let z = norm.powf(exponent);
 
         let z_x = multiplier[0] * f64::copysign(z * angle_cos, mouse[0]);
-        let z_y = multiplier[0] * f64::copysign(z * angle_sin, mouse[1]);
+        let z_y = multiplier[1] * f64::copysign(z * angle_sin, mouse[1]);
 
         let raw_output = [
             Self::update_axis(&mut axis[0], dead_zone[0], z_x, max_axis, min_axis),
             Self::update_axis(&mut axis[1], dead_zone[1], z_y, max_axis, min_axis),
         ];
+        // the residue works on the centered values, the controller on the shifted ones
+        let centered_axis = axis;
 
         self.remainder[0] = Self::update_controller_axis(
-            ds4,
-            &self.output_x,
+            controller,
+            self.output_x,
             &mut axis[0],
             &mut self.axis_hist[0],
             min_axis,
         );
         self.remainder[1] = Self::update_controller_axis(
-            ds4,
-            &self.output_y,
+            controller,
+            self.output_y,
             &mut axis[1],
             &mut self.axis_hist[1],
             min_axis,
         );
 
-        self.update_residue(mouse, axis, axis_scale, multiplier, raw_output);
+        self.update_residue(mouse, centered_axis, axis_scale, multiplier, raw_output);
     }
 
     fn update_axis(axis: &mut i32, dead_zone: f64, z: f64, max_axis: i32, min_axis: i32) -> f64 {
@@ -204,8 +611,8 @@ impl MouseMapping {
     }
 
     fn update_controller_axis(
-        ds4: &mut DS4,
-        output: &str,
+        controller: &mut dyn Controller,
+        output: Axis,
         axis: &mut i32,
         axis_hist: &mut Vec<i32>,
         min_axis: i32,
@@ -223,7 +630,7 @@ impl MouseMapping {
 
         // update controller state
         let _axis = *axis;
-        ds4.set_axis(output, _axis as u8);
+        controller.set_axis(output, _axis as u8);
 
         axis_hist.push(_axis);
         if axis_hist.len() > 256 {
@@ -244,10 +651,10 @@ impl MouseMapping {
         let mut input_trunk = [0f64; 2];
 
         if axis[0] != 0 || axis[1] != 0 {
-            let zx = axis[0].abs() as f64;
+            let mut zx = axis[0].abs() as f64;
             let mut zy = axis[1].abs() as f64;
 
-            let mut dead_zone = [
+            let dead_zone = [
                 self.dead_zone_x as f64 * axis_scale,
                 self.dead_zone_y as f64 * axis_scale,
             ];
@@ -261,6 +668,14 @@ impl MouseMapping {
                     multiplier[1] * output_raw[1],
                 );
             } else if zy == 0f64 {
+                zx -= dead_zone[0];
+                zx = zx.max(0f64);
+                input_trunk[0] = f64::copysign(
+                    (zx / (multiplier[0].abs() * FREQUENCY_SCALE.powf(self.exponent)))
+                        .powf(1f64 / self.exponent),
+                    multiplier[0] * output_raw[0],
+                );
+            } else {
                 /*
                  * approximate the residue vector angle:
                  *
@@ -278,15 +693,12 @@ impl MouseMapping {
                 let angle_cos = angle.cos();
                 let angle_sin = angle.sin();
 
-                if self.shape == "circle" {
-                    dead_zone[0] *= angle_cos;
-                    dead_zone[1] *= angle_sin;
-                }
+                let dead_zone = self.shape.dead_zone(dead_zone, angle_cos, angle_sin);
 
-                let normx = ((zx - dead_zone[0])
+                let normx = ((zx - dead_zone[0]).max(0f64)
                     / (multiplier[0].abs() * FREQUENCY_SCALE.powf(self.exponent) * angle_cos))
                     .powf(1.0 / self.exponent);
-                let normy = ((zy - dead_zone[1])
+                let normy = ((zy - dead_zone[1]).max(0f64)
                     / (multiplier[1].abs() * FREQUENCY_SCALE.powf(self.exponent) * angle_sin))
                     .powf(1.0 / self.exponent);
                 input_trunk[0] = f64::copysign(angle_cos * normx, multiplier[0] * output_raw[0]);
@@ -307,39 +719,2362 @@ impl MouseMapping {
     }
 }
 
+// gyro units per degree per second
+const GYRO_RESOLUTION: f64 = 16.384;
+
+/// Translates mouse movement into angular velocity.
+/// `scale_x` and `scale_y` are the degrees the controller is rotated per mouse count.
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum Mapping {
-    Button(ButtonMapping),
-    Axis(AxisMapping),
-    Mouse(MouseMapping),
+pub struct GyroMapping {
+    scale_x: f64,
+    scale_y: f64,
+
+    // internal state
+    #[serde(skip)]
+    last_frame: Option<Instant>,
 }
 
-pub struct Mapper {
-    mappings: Vec<Mapping>,
+impl GyroMapping {
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+    ) {
+        // reports can arrive in bursts, clamp the frame time so the velocity does not explode
+        let frame_time = self
+            .last_frame
+            .map(|last_frame| now.duration_since(last_frame))
+            .unwrap_or(Duration::from_millis(4))
+            .max(Duration::from_millis(1))
+            .as_secs_f64();
+        self.last_frame = Some(now);
+
+        // moving the mouse right/down turns the controller right/down
+        let yaw = -raw_input.mouse_x() as f64 * self.scale_x / frame_time * GYRO_RESOLUTION;
+        let pitch = -raw_input.mouse_y() as f64 * self.scale_y / frame_time * GYRO_RESOLUTION;
+
+        controller.set_gyro([
+            pitch.clamp(i16::MIN as f64, i16::MAX as f64) as i16,
+            yaw.clamp(i16::MIN as f64, i16::MAX as f64) as i16,
+            0,
+        ]);
+    }
 }
 
-impl Mapper {
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
-        let contents = std::fs::read_to_string(path).map_err(|_| "unable to open mappings file")?;
-        let mappings: Vec<Mapping> =
-            serde_json::from_str(&contents).map_err(|_| "unable to parse mappings file")?;
-        Ok(Self { mappings })
+/// Direction the mouse has to be flicked in for a `MouseGesture` mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+fn default_gesture_duration() -> u64 {
+    50
+}
+
+fn default_gesture_cooldown() -> u64 {
+    250
+}
+
+/// Presses the output when the mouse moves faster than `threshold` counts per second
+/// in `direction`, like flicking a stick.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MouseGestureMapping {
+    direction: GestureDirection,
+    threshold: f64,
+    output: Button,
+    /// Milliseconds the output is pressed for.
+    #[serde(default = "default_gesture_duration")]
+    duration: u64,
+    /// Milliseconds after firing in which further flicks are ignored.
+    #[serde(default = "default_gesture_cooldown")]
+    cooldown: u64,
+
+    // internal state
+    #[serde(skip)]
+    last_frame: Option<Instant>,
+    #[serde(skip)]
+    fired_at: Option<Instant>,
+}
+
+impl MouseGestureMapping {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.threshold <= 0f64 {
+            return Err("threshold has to be above 0");
+        }
+        Ok(())
     }
 
-    pub fn map_controller(&mut self, raw_input: &RawInput, ds4: &mut DS4) {
-        for mapping in self.mappings.iter_mut() {
-            match mapping {
-                Mapping::Button(mapping) => {
-                    mapping.map_controller(raw_input, ds4);
-                }
-                Mapping::Axis(mapping) => {
-                    mapping.map_controller(raw_input, ds4);
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+    ) {
+        // same frame time clamping as the gyro, bursts must not look like flicks
+        let frame_time = self
+            .last_frame
+            .map(|last_frame| now.duration_since(last_frame))
+            .unwrap_or(Duration::from_millis(4))
+            .max(Duration::from_millis(1))
+            .as_secs_f64();
+        self.last_frame = Some(now);
+
+        let distance = match self.direction {
+            GestureDirection::Left => -raw_input.mouse_x(),
+            GestureDirection::Right => raw_input.mouse_x(),
+            GestureDirection::Up => -raw_input.mouse_y(),
+            GestureDirection::Down => raw_input.mouse_y(),
+        };
+        let velocity = distance as f64 / frame_time;
+
+        let cooling_down = match self.fired_at {
+            Some(fired_at) => now.duration_since(fired_at) < Duration::from_millis(self.cooldown),
+            None => false,
+        };
+        if velocity >= self.threshold && !cooling_down {
+            self.fired_at = Some(now);
+        }
+
+        if let Some(fired_at) = self.fired_at {
+            if now.duration_since(fired_at) < Duration::from_millis(self.duration) {
+                controller.set_btn(self.output, true);
+            }
+        }
+    }
+}
+
+fn default_touch_sensitivity() -> f64 {
+    1f64
+}
+
+/// Describes where the finger of a `TouchpadMapping` is placed while the input is held.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "contact", rename_all = "snake_case")]
+pub enum TouchContact {
+    /// finger is placed at `origin` (or the center of the touchpad) and follows the mouse
+    Mouse {
+        origin: Option<[u16; 2]>,
+        #[serde(default = "default_touch_sensitivity")]
+        sensitivity: f64,
+    },
+    /// finger rests at a fixed position
+    Fixed { position: [u16; 2] },
+    /// finger moves from `from` to `to` within `duration` milliseconds
+    Swipe {
+        from: [u16; 2],
+        to: [u16; 2],
+        duration: u64,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TouchpadMapping {
+    input: Chord,
+    #[serde(default)]
+    finger: usize,
+    #[serde(default)]
+    click: bool,
+    #[serde(flatten)]
+    contact: TouchContact,
+
+    // internal state
+    #[serde(skip)]
+    position: Option<[f64; 2]>,
+    #[serde(skip)]
+    pressed_at: Option<Instant>,
+    #[serde(skip)]
+    touch_id: u8,
+}
+
+impl TouchpadMapping {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.finger > 1 {
+            return Err("finger has to be either 0 or 1");
+        }
+        let positions = match &self.contact {
+            TouchContact::Mouse { origin, .. } => origin.iter().collect::<Vec<_>>(),
+            TouchContact::Fixed { position } => vec![position],
+            TouchContact::Swipe { from, to, .. } => vec![from, to],
+        };
+        // the ds4 touchpad is the smallest one, positions within it are valid on every controller
+        if positions
+            .iter()
+            .any(|p| p[0] >= TOUCHPAD_WIDTH || p[1] >= TOUCHPAD_HEIGHT)
+        {
+            return Err("touch position is outside of the touchpad");
+        }
+        Ok(())
+    }
+
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+    ) {
+        if !self.input.is_down(raw_input) {
+            self.position = None;
+            self.pressed_at = None;
+            return;
+        }
+
+        let pressed_at = match self.pressed_at {
+            Some(pressed_at) => pressed_at,
+            None => {
+                // every new contact gets a new tracking id
+                self.touch_id = (self.touch_id + 1) & 0x7F;
+                self.pressed_at = Some(now);
+                now
+            }
+        };
+
+        let position = match &self.contact {
+            TouchContact::Mouse {
+                origin,
+                sensitivity,
+            } => {
+                let position = self.position.unwrap_or_else(|| match origin {
+                    Some(origin) => [origin[0] as f64, origin[1] as f64],
+                    None => {
+                        let touchpad_size = controller.touchpad_size();
+                        [
+                            touchpad_size[0] as f64 / 2f64,
+                            touchpad_size[1] as f64 / 2f64,
+                        ]
+                    }
+                });
+                [
+                    position[0] + raw_input.mouse_x() as f64 * sensitivity,
+                    position[1] + raw_input.mouse_y() as f64 * sensitivity,
+                ]
+            }
+            TouchContact::Fixed { position } => [position[0] as f64, position[1] as f64],
+            TouchContact::Swipe { from, to, duration } => {
+                let progress = if *duration > 0 {
+                    (now.duration_since(pressed_at).as_secs_f64()
+                        / Duration::from_millis(*duration).as_secs_f64())
+                    .min(1f64)
+                } else {
+                    1f64
+                };
+                [
+                    from[0] as f64 + (to[0] as f64 - from[0] as f64) * progress,
+                    from[1] as f64 + (to[1] as f64 - from[1] as f64) * progress,
+                ]
+            }
+        };
+        let touchpad_size = controller.touchpad_size();
+        let position = [
+            position[0].clamp(0f64, (touchpad_size[0] - 1) as f64),
+            position[1].clamp(0f64, (touchpad_size[1] - 1) as f64),
+        ];
+        self.position = Some(position);
+
+        controller.set_touch_point(
+            self.finger,
+            TouchPoint {
+                id: self.touch_id,
+                active: true,
+                x: position[0] as u16,
+                y: position[1] as u16,
+            },
+        );
+        if self.click {
+            controller.set_btn(Button::Touch, true);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroStep {
+    /// Holds the button down until it is released or the macro ends.
+    Press(Button),
+    /// Stops holding the button, other mappings may still press it.
+    Release(Button),
+    /// Holds the axis at `value` (-1 - 1) until it is changed or the macro ends.
+    Axis { output: Axis, value: f32 },
+    /// Waits the given number of milliseconds before the next step.
+    Wait(u64),
+}
+
+/// What happens when a macro is triggered while it is still running.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroRetrigger {
+    /// Plays the macro again once the current run is done.
+    #[default]
+    Queue,
+    /// Starts over from the first step.
+    Restart,
+}
+
+/// Plays a sequence of steps onto the controller, one report at a time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MacroMapping {
+    input: Chord,
+    steps: Vec<MacroStep>,
+    /// Stops the macro as soon as the input is released.
+    #[serde(default)]
+    cancel_on_release: bool,
+    /// Starts over while the input is still held at the end.
+    #[serde(default)]
+    repeat: bool,
+    #[serde(default)]
+    retrigger: MacroRetrigger,
+
+    // internal state
+    #[serde(skip)]
+    was_down: bool,
+    #[serde(skip)]
+    running: bool,
+    #[serde(skip)]
+    queued: usize,
+    #[serde(skip)]
+    step: usize,
+    #[serde(skip)]
+    wait_started: Option<Instant>,
+    #[serde(skip)]
+    buttons: Vec<Button>,
+    #[serde(skip)]
+    axes: Vec<(Axis, u8)>,
+}
+
+impl MacroMapping {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.steps.is_empty() {
+            return Err("a macro needs at least one step");
+        }
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                MacroStep::Press(button) | MacroStep::Release(button) => {
+                    Some(Output::Button(*button))
                 }
-                Mapping::Mouse(mapping) => {
-                    mapping.map_controller(raw_input, ds4);
+                MacroStep::Axis { output, .. } => Some(Output::Axis(*output)),
+                MacroStep::Wait(_) => None,
+            })
+            .collect()
+    }
+
+    fn start(&mut self) {
+        self.stop();
+        self.running = true;
+    }
+
+    // releases everything the macro holds
+    fn stop(&mut self) {
+        self.running = false;
+        self.step = 0;
+        self.wait_started = None;
+        self.buttons.clear();
+        self.axes.clear();
+    }
+
+    fn reset(&mut self) {
+        self.stop();
+        self.queued = 0;
+    }
+
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+    ) {
+        let down = self.input.is_down(raw_input);
+        let pressed = down && !self.was_down;
+        self.was_down = down;
+
+        if self.running && self.cancel_on_release && !down {
+            self.reset();
+        }
+
+        // the last step has been shown for a report, see what comes next
+        if self.running && self.step == self.steps.len() {
+            self.stop();
+            if self.repeat && down {
+                self.start();
+            } else if self.queued > 0 {
+                self.queued -= 1;
+                self.start();
+            }
+        }
+
+        if pressed {
+            if !self.running {
+                self.start();
+            } else {
+                match self.retrigger {
+                    MacroRetrigger::Queue => self.queued += 1,
+                    MacroRetrigger::Restart => self.start(),
                 }
             }
         }
+
+        if !self.running {
+            return;
+        }
+
+        while let Some(step) = self.steps.get(self.step).copied() {
+            match step {
+                MacroStep::Press(button) => {
+                    if !self.buttons.contains(&button) {
+                        self.buttons.push(button);
+                    }
+                }
+                MacroStep::Release(button) => {
+                    self.buttons.retain(|b| *b != button);
+                }
+                MacroStep::Axis { output, value } => {
+                    let value = ((0.5f32 + value.clamp(-1f32, 1f32) / 2f32) * 255f32) as u8;
+                    self.axes.retain(|(axis, _)| *axis != output);
+                    self.axes.push((output, value));
+                }
+                MacroStep::Wait(duration) => {
+                    let wait_started = *self.wait_started.get_or_insert(now);
+                    if now.duration_since(wait_started) < Duration::from_millis(duration) {
+                        break;
+                    }
+                    self.wait_started = None;
+                }
+            }
+            self.step += 1;
+        }
+
+        for button in self.buttons.iter() {
+            controller.set_btn(*button, true);
+        }
+        for (axis, value) in self.axes.iter() {
+            controller.set_axis(*axis, *value);
+        }
+    }
+}
+
+fn default_hold_time() -> u64 {
+    200
+}
+
+fn default_double_tap_time() -> u64 {
+    250
+}
+
+fn default_tap_duration() -> u64 {
+    50
+}
+
+/// Where a `TapHold` mapping is in telling taps, holds and double taps apart.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum TapState {
+    #[default]
+    Idle,
+    Pressed {
+        since: Instant,
+    },
+    Held,
+    Released {
+        at: Instant,
+    },
+    DoubleTapped,
+}
+
+/// Drives different buttons depending on whether the input is tapped,
+/// held for `hold_time` or tapped twice within `double_tap_time` milliseconds.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TapHoldMapping {
+    input: Chord,
+    tap: Button,
+    #[serde(default)]
+    hold: Option<Button>,
+    #[serde(default)]
+    double_tap: Option<Button>,
+    #[serde(default = "default_hold_time")]
+    hold_time: u64,
+    #[serde(default = "default_double_tap_time")]
+    double_tap_time: u64,
+    /// A tap is only known once the input is released, the output is pressed this long then.
+    #[serde(default = "default_tap_duration")]
+    tap_duration: u64,
+
+    // internal state
+    #[serde(skip)]
+    state: TapState,
+    #[serde(skip)]
+    pulse: Option<(Button, Instant)>,
+}
+
+impl TapHoldMapping {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.hold.is_none() && self.double_tap.is_none() {
+            return Err("a TapHold mapping needs a hold or double_tap output");
+        }
+        if self.tap_duration == 0 {
+            return Err("tap_duration has to be above 0");
+        }
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        let mut outputs = vec![Output::Button(self.tap)];
+        outputs.extend(self.hold.map(Output::Button));
+        outputs.extend(self.double_tap.map(Output::Button));
+        outputs
+    }
+
+    fn reset(&mut self) {
+        self.state = TapState::Idle;
+        self.pulse = None;
+    }
+
+    fn start_pulse(&mut self, button: Button, now: Instant) {
+        self.pulse = Some((button, now + Duration::from_millis(self.tap_duration)));
+    }
+
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+    ) {
+        let down = self.input.is_down(raw_input);
+
+        self.state = match self.state {
+            TapState::Idle if down => TapState::Pressed { since: now },
+            TapState::Idle => TapState::Idle,
+            TapState::Pressed { since } => match self.hold {
+                Some(_)
+                    if down
+                        && now.duration_since(since) >= Duration::from_millis(self.hold_time) =>
+                {
+                    TapState::Held
+                }
+                _ if down => TapState::Pressed { since },
+                _ if self.double_tap.is_some() => TapState::Released { at: now },
+                _ => {
+                    self.start_pulse(self.tap, now);
+                    TapState::Idle
+                }
+            },
+            TapState::Held if down => TapState::Held,
+            TapState::Held => TapState::Idle,
+            TapState::Released { .. } if down => {
+                if let Some(double_tap) = self.double_tap {
+                    self.start_pulse(double_tap, now);
+                }
+                TapState::DoubleTapped
+            }
+            TapState::Released { at } => {
+                if now.duration_since(at) >= Duration::from_millis(self.double_tap_time) {
+                    self.start_pulse(self.tap, now);
+                    TapState::Idle
+                } else {
+                    TapState::Released { at }
+                }
+            }
+            TapState::DoubleTapped if down => TapState::DoubleTapped,
+            TapState::DoubleTapped => TapState::Idle,
+        };
+
+        match self.state {
+            TapState::Held => {
+                if let Some(hold) = self.hold {
+                    controller.set_btn(hold, true);
+                }
+            }
+            TapState::DoubleTapped => {
+                if let Some(double_tap) = self.double_tap {
+                    controller.set_btn(double_tap, true);
+                }
+            }
+            _ => {}
+        }
+
+        if let Some((button, until)) = self.pulse {
+            if now < until {
+                controller.set_btn(button, true);
+            } else {
+                self.pulse = None;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Mapping {
+    Button(ButtonMapping),
+    Axis(AxisMapping),
+    KeyStick(KeyStickMapping),
+    Mouse(MouseMapping),
+    Touchpad(TouchpadMapping),
+    Gyro(GyroMapping),
+    MouseGesture(MouseGestureMapping),
+    Macro(MacroMapping),
+    TapHold(TapHoldMapping),
+}
+
+/// Something a mapping reads from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    Key(Key),
+    Mouse,
+}
+
+impl From<&Chord> for Vec<Input> {
+    fn from(chord: &Chord) -> Self {
+        chord.keys().iter().copied().map(Input::Key).collect()
+    }
+}
+
+/// Something a mapping writes to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Button(Button),
+    Axis(Axis),
+    Touch(usize),
+    Gyro,
+}
+
+impl Mapping {
+    fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            Mapping::Button(mapping) => mapping.validate(),
+            Mapping::Touchpad(mapping) => mapping.validate(),
+            Mapping::MouseGesture(mapping) => mapping.validate(),
+            Mapping::Macro(mapping) => mapping.validate(),
+            Mapping::TapHold(mapping) => mapping.validate(),
+            _ => Ok(()),
+        }
+    }
+
+    fn inputs(&self) -> Vec<Input> {
+        match self {
+            Mapping::Button(mapping) => Vec::from(&mapping.input),
+            Mapping::Axis(mapping) => Vec::from(&mapping.input),
+            Mapping::KeyStick(mapping) => mapping.inputs(),
+            Mapping::Mouse(_) => vec![Input::Mouse],
+            Mapping::Touchpad(mapping) => Vec::from(&mapping.input),
+            Mapping::Gyro(_) => vec![Input::Mouse],
+            Mapping::MouseGesture(_) => vec![Input::Mouse],
+            Mapping::Macro(mapping) => Vec::from(&mapping.input),
+            Mapping::TapHold(mapping) => Vec::from(&mapping.input),
+        }
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        match self {
+            Mapping::Button(mapping) => vec![Output::Button(mapping.output)],
+            Mapping::Axis(mapping) => vec![Output::Axis(mapping.output)],
+            Mapping::KeyStick(mapping) => vec![
+                Output::Axis(mapping.output_x),
+                Output::Axis(mapping.output_y),
+            ],
+            Mapping::Mouse(mapping) => vec![
+                Output::Axis(mapping.output_x),
+                Output::Axis(mapping.output_y),
+            ],
+            Mapping::Touchpad(mapping) => {
+                let mut outputs = vec![Output::Touch(mapping.finger)];
+                if mapping.click {
+                    outputs.push(Output::Button(Button::Touch));
+                }
+                outputs
+            }
+            Mapping::Gyro(_) => vec![Output::Gyro],
+            Mapping::MouseGesture(mapping) => vec![Output::Button(mapping.output)],
+            Mapping::Macro(mapping) => mapping.outputs(),
+            Mapping::TapHold(mapping) => mapping.outputs(),
+        }
+    }
+
+    // releases everything the mapping latched or holds
+    fn reset(&mut self) {
+        match self {
+            Mapping::Button(mapping) => mapping.latch.reset(),
+            Mapping::Axis(mapping) => mapping.reset(),
+            Mapping::KeyStick(mapping) => mapping.reset(),
+            Mapping::Macro(mapping) => mapping.reset(),
+            Mapping::TapHold(mapping) => mapping.reset(),
+            _ => {}
+        }
+    }
+
+    // the chord if it is held down and suppresses mappings on a part of it
+    fn exclusive_chord(&self, raw_input: &RawInput) -> Option<&Chord> {
+        let (chord, exclusive) = match self {
+            Mapping::Button(mapping) => (&mapping.input, mapping.exclusive),
+            Mapping::Axis(mapping) => (&mapping.input, mapping.exclusive),
+            _ => return None,
+        };
+        if exclusive && chord.is_down(raw_input) {
+            Some(chord)
+        } else {
+            None
+        }
+    }
+
+    // true if all keys the mapping reads are a strict subset of the chord
+    fn is_part_of(&self, chord: &Chord) -> bool {
+        let inputs = self.inputs();
+        inputs.len() < chord.keys().len()
+            && !inputs.is_empty()
+            && inputs.iter().all(|input| match input {
+                Input::Key(key) => chord.keys().contains(key),
+                Input::Mouse => false,
+            })
+    }
+
+    // the axis an active `Axis` mapping writes this frame
+    fn claimed_axis(&self, raw_input: &RawInput) -> Option<Axis> {
+        match self {
+            Mapping::Axis(mapping) if mapping.is_active(raw_input) => Some(mapping.output),
+            _ => None,
+        }
+    }
+
+    fn map_controller(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+        scale: Scale,
+        claimed: &[Axis],
+    ) {
+        match self {
+            Mapping::Button(mapping) => {
+                mapping.map_controller(raw_input, controller, now);
+            }
+            Mapping::Axis(mapping) => {
+                mapping.map_controller(raw_input, controller, now, scale, claimed);
+            }
+            Mapping::KeyStick(mapping) => {
+                mapping.map_controller(raw_input, controller, scale);
+            }
+            Mapping::Mouse(mapping) => {
+                mapping.map_controller(raw_input, controller, scale);
+            }
+            Mapping::Touchpad(mapping) => {
+                mapping.map_controller(raw_input, controller, now);
+            }
+            Mapping::Gyro(mapping) => {
+                mapping.map_controller(raw_input, controller, now);
+            }
+            Mapping::MouseGesture(mapping) => {
+                mapping.map_controller(raw_input, controller, now);
+            }
+            Mapping::Macro(mapping) => {
+                mapping.map_controller(raw_input, controller, now);
+            }
+            Mapping::TapHold(mapping) => {
+                mapping.map_controller(raw_input, controller, now);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayerActivation {
+    /// Active while the key is held down.
+    #[default]
+    Hold,
+    /// Every press of the key switches the layer on or off.
+    Toggle,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LayerConfig {
+    name: String,
+    key: Key,
+    #[serde(default)]
+    activation: LayerActivation,
+    #[serde(default)]
+    mappings: Vec<serde_json::Value>,
+}
+
+/// A named group of mappings on top of the active profile.
+/// While a layer is active its mappings hide the mappings of lower layers and the profile
+/// that use the same input or drive the same output.
+pub struct Layer {
+    name: String,
+    key: Key,
+    activation: LayerActivation,
+    mappings: Vec<Mapping>,
+
+    // internal state
+    active: bool,
+    key_down: bool,
+}
+
+impl Layer {
+    // returns true if the layer got activated or deactivated
+    fn update(&mut self, raw_input: &RawInput) -> bool {
+        let key_down = raw_input.key(self.key);
+        let active = match self.activation {
+            LayerActivation::Hold => key_down,
+            LayerActivation::Toggle if key_down && !self.key_down => !self.active,
+            LayerActivation::Toggle => self.active,
+        };
+        self.key_down = key_down;
+
+        let changed = active != self.active;
+        self.active = active;
+        changed
+    }
+}
+
+fn default_modifier_scale() -> f64 {
+    1f64
+}
+
+/// Scales analog outputs down while `key` is held, like a walk or precision aim key.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Modifier {
+    key: Chord,
+    /// Factor for stick deflection driven by keys.
+    #[serde(default = "default_modifier_scale")]
+    stick: f64,
+    /// Factor for the sensitivity of mouse mappings.
+    #[serde(default = "default_modifier_scale")]
+    mouse: f64,
+}
+
+impl Modifier {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.stick < 0f64 || self.stick > 1f64 {
+            return Err("stick has to be between 0 and 1");
+        }
+        if self.mouse <= 0f64 {
+            return Err("mouse has to be above 0");
+        }
+        Ok(())
+    }
+}
+
+/// The factors of all held modifiers multiplied together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scale {
+    stick: f32,
+    mouse: f64,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self {
+            stick: 1f32,
+            mouse: 1f64,
+        }
+    }
+}
+
+impl Scale {
+    fn of(modifiers: &[Modifier], raw_input: &RawInput) -> Self {
+        modifiers
+            .iter()
+            .filter(|modifier| modifier.key.is_down(raw_input))
+            .fold(Self::default(), |scale, modifier| Self {
+                stick: scale.stick * modifier.stick as f32,
+                mouse: scale.mouse * modifier.mouse,
+            })
+    }
+}
+
+fn default_wheel_pulse() -> u64 {
+    50
+}
+
+// the object form of a mappings file, the mappings come with settings that apply to all of them
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MappingsFile {
+    #[serde(default)]
+    mappings: Vec<serde_json::Value>,
+    #[serde(default)]
+    profiles: HashMap<String, ProfileConfig>,
+    #[serde(default)]
+    lightbar: Vec<LightbarRule>,
+    #[serde(default)]
+    layers: Vec<LayerConfig>,
+    #[serde(default)]
+    modifiers: Vec<Modifier>,
+    #[serde(default = "default_wheel_pulse")]
+    wheel_pulse: u64,
+    #[serde(default)]
+    virtual_controller: Option<DeviceIdentity>,
+    #[serde(default)]
+    feedback: Option<FeedbackConfig>,
+}
+
+// a profile is a plain list of mappings or an object that also overrides the feedback settings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProfileConfig {
+    Mappings(Vec<serde_json::Value>),
+    Object(ProfileObject),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileObject {
+    #[serde(default)]
+    mappings: Vec<serde_json::Value>,
+    #[serde(default)]
+    feedback: Option<FeedbackOverride>,
+}
+
+impl ProfileConfig {
+    fn into_parts(self) -> (Vec<serde_json::Value>, Option<FeedbackOverride>) {
+        match self {
+            ProfileConfig::Mappings(mappings) => (mappings, None),
+            ProfileConfig::Object(profile) => (profile.mappings, profile.feedback),
+        }
+    }
+}
+
+/// Switches to `profile` while the lightbar colour lies between `min` and `max`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LightbarRule {
+    min: [u8; 3],
+    max: [u8; 3],
+    profile: String,
+}
+
+impl LightbarRule {
+    fn matches(&self, color: [u8; 3]) -> bool {
+        (0..3).all(|i| self.min[i] <= color[i] && color[i] <= self.max[i])
+    }
+}
+
+/// Why a mappings file could not be loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// The file as a whole is broken, e.g. it is no valid json or refers to an unknown profile.
+    File(&'static str),
+    /// One entry of a list of mappings was rejected.
+    Mapping(InvalidMapping),
+}
+
+impl From<&'static str> for LoadError {
+    fn from(err: &'static str) -> Self {
+        LoadError::File(err)
+    }
+}
+
+impl From<LoadError> for &'static str {
+    fn from(err: LoadError) -> Self {
+        match err {
+            LoadError::File(err) => err,
+            LoadError::Mapping(_) => "invalid mapping in mappings file",
+        }
+    }
+}
+
+/// A mapping that was rejected along with where it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidMapping {
+    /// Position in its list of mappings, starting at 1.
+    pub index: usize,
+    /// The profile or layer the mapping belongs to, none for the default profile.
+    pub context: Option<String>,
+    pub entry: serde_json::Value,
+    /// The type, input or output name that does not exist, if that is why it was rejected.
+    pub unknown_name: Option<String>,
+    pub reason: String,
+}
+
+// serde reports names missing from an enum as "unknown variant `name`, expected ..."
+fn unknown_name(reason: &str) -> Option<String> {
+    let name = reason.strip_prefix("unknown variant `")?;
+    name.find('`').map(|end| name[..end].to_string())
+}
+
+// parse every entry on its own so errors can point at the offending mapping
+fn parse_mappings(
+    entries: Vec<serde_json::Value>,
+    context: Option<&str>,
+) -> Result<Vec<Mapping>, LoadError> {
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            Mapping::deserialize(&entry)
+                .map_err(|err| err.to_string())
+                .and_then(|mapping| mapping.validate().map(|_| mapping).map_err(String::from))
+                .map_err(|reason| {
+                    match context {
+                        Some(context) => error!(
+                            "invalid mapping #{} in {} {}: {}",
+                            i + 1,
+                            context,
+                            entry,
+                            reason
+                        ),
+                        None => error!("invalid mapping #{} {}: {}", i + 1, entry, reason),
+                    }
+                    LoadError::Mapping(InvalidMapping {
+                        index: i + 1,
+                        context: context.map(String::from),
+                        unknown_name: unknown_name(&reason),
+                        entry,
+                        reason,
+                    })
+                })
+        })
+        .collect()
+}
+
+pub struct Mapper {
+    // mappings of the default profile
+    mappings: Vec<Mapping>,
+    profiles: HashMap<String, Vec<Mapping>>,
+    lightbar: Vec<LightbarRule>,
+    active_profile: Option<String>,
+    // ordered from bottom to top
+    layers: Vec<Layer>,
+    modifiers: Vec<Modifier>,
+    // how long every notch of the mouse wheel presses its key
+    #[cfg_attr(not(windows), allow(dead_code))]
+    wheel_pulse: Duration,
+    mouse_lock: bool,
+    virtual_controller: Option<DeviceIdentity>,
+    feedback: Feedback,
+    // feedback of the profiles that override the settings of the file
+    profile_feedback: HashMap<String, Feedback>,
+}
+
+impl FromStr for Mapper {
+    type Err = LoadError;
+
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        let document: serde_json::Value = serde_json::from_str(contents).map_err(|err| {
+            error!("unable to parse mappings file: {}", err);
+            "unable to parse mappings file"
+        })?;
+
+        // a mappings file is either a plain list of mappings or an object with further settings
+        let file = match document {
+            serde_json::Value::Array(mappings) => MappingsFile {
+                mappings,
+                profiles: HashMap::new(),
+                lightbar: Vec::new(),
+                layers: Vec::new(),
+                modifiers: Vec::new(),
+                wheel_pulse: default_wheel_pulse(),
+                virtual_controller: None,
+                feedback: None,
+            },
+            document => MappingsFile::deserialize(&document).map_err(|err| {
+                error!("invalid mappings file: {}", err);
+                "invalid mappings file"
+            })?,
+        };
+
+        if let Some(identity) = &file.virtual_controller {
+            identity.validate().map_err(|err| {
+                error!("invalid virtual controller: {}", err);
+                "invalid virtual controller in mappings file"
+            })?;
+        }
+        if let Some(feedback) = &file.feedback {
+            feedback.validate().map_err(|err| {
+                error!("invalid feedback: {}", err);
+                "invalid feedback in mappings file"
+            })?;
+        }
+        if file.wheel_pulse == 0 {
+            error!("wheel_pulse has to be above 0");
+            return Err(LoadError::File("invalid wheel_pulse in mappings file"));
+        }
+        for modifier in file.modifiers.iter() {
+            modifier.validate().map_err(|err| {
+                error!("invalid modifier `{}`: {}", modifier.key, err);
+                "invalid modifier in mappings file"
+            })?;
+        }
+        for rule in file.lightbar.iter() {
+            if !file.profiles.contains_key(&rule.profile) {
+                error!(
+                    "lightbar rule switches to unknown profile `{}`",
+                    rule.profile
+                );
+                return Err(LoadError::File("invalid lightbar rule in mappings file"));
+            }
+            if (0..3).any(|i| rule.min[i] > rule.max[i]) {
+                error!(
+                    "lightbar rule for profile `{}` has min above max",
+                    rule.profile
+                );
+                return Err(LoadError::File("invalid lightbar rule in mappings file"));
+            }
+        }
+
+        let feedback = file.feedback.unwrap_or_default();
+        let mut profile_feedback = HashMap::new();
+        let mappings = parse_mappings(file.mappings, None)?;
+        let profiles = file
+            .profiles
+            .into_iter()
+            .map(|(name, profile)| {
+                let (entries, feedback_override) = profile.into_parts();
+                if let Some(feedback_override) = feedback_override {
+                    let feedback = feedback.merge(&feedback_override);
+                    feedback.validate().map_err(|err| {
+                        error!("invalid feedback in profile `{}`: {}", name, err);
+                        "invalid feedback in mappings file"
+                    })?;
+                    profile_feedback.insert(name.clone(), Feedback::from(feedback));
+                }
+                let mappings = parse_mappings(entries, Some(&format!("profile `{}`", name)))?;
+                Ok((name, mappings))
+            })
+            .collect::<Result<HashMap<_, _>, LoadError>>()?;
+        let layers = file
+            .layers
+            .into_iter()
+            .map(|layer| {
+                let mappings =
+                    parse_mappings(layer.mappings, Some(&format!("layer `{}`", layer.name)))?;
+                Ok(Layer {
+                    name: layer.name,
+                    key: layer.key,
+                    activation: layer.activation,
+                    mappings,
+                    active: false,
+                    key_down: false,
+                })
+            })
+            .collect::<Result<Vec<_>, LoadError>>()?;
+        Ok(Self {
+            mappings,
+            profiles,
+            lightbar: file.lightbar,
+            active_profile: None,
+            layers,
+            modifiers: file.modifiers,
+            wheel_pulse: Duration::from_millis(file.wheel_pulse),
+            mouse_lock: false,
+            virtual_controller: file.virtual_controller,
+            feedback: Feedback::from(feedback),
+            profile_feedback,
+        })
+    }
+}
+
+impl Mapper {
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let contents = std::fs::read_to_string(path).map_err(|_| "unable to open mappings file")?;
+        contents.parse()
+    }
+
+    /// Reacts to the feedback the game sent most recently: the lightbar colour picks
+    /// the active profile and rumble goes to the feedback sinks of that profile.
+    pub fn update_output(&mut self, output_state: &OutputState, now: Instant) {
+        // nothing to go by until the game set the lightbar
+        if output_state.updated_at.is_some() {
+            let profile = self
+                .lightbar
+                .iter()
+                .find(|rule| rule.matches(output_state.lightbar))
+                .map(|rule| rule.profile.clone());
+            if profile != self.active_profile {
+                info!(
+                    "lightbar {:?} switched to profile `{}`",
+                    output_state.lightbar,
+                    profile.as_deref().unwrap_or("default")
+                );
+                // a rumble reported by the sinks of the previous profile ends with it
+                let overrides = |profile: &Option<String>| match profile {
+                    Some(profile) => self.profile_feedback.contains_key(profile),
+                    None => false,
+                };
+                if overrides(&self.active_profile) || overrides(&profile) {
+                    self.active_feedback().stop(now);
+                }
+                self.active_profile = profile;
+            }
+        }
+
+        self.active_feedback().update(output_state, now);
+    }
+
+    fn active_feedback(&mut self) -> &mut Feedback {
+        let profile_feedback = match &self.active_profile {
+            Some(profile) => self.profile_feedback.get_mut(profile),
+            None => None,
+        };
+        match profile_feedback {
+            Some(feedback) => feedback,
+            None => &mut self.feedback,
+        }
+    }
+
+    /// Identity the virtual controller should present, if the mappings file overrides it.
+    pub fn virtual_controller(&self) -> Option<&DeviceIdentity> {
+        self.virtual_controller.as_ref()
+    }
+
+    /// How long a notch of the mouse wheel holds its `wheel_*` key down.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn wheel_pulse(&self) -> Duration {
+        self.wheel_pulse
+    }
+
+    /// Names of the layers that are currently active, from bottom to top.
+    pub fn active_layers(&self) -> Vec<&str> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.active)
+            .map(|layer| layer.name.as_str())
+            .collect()
+    }
+
+    fn update_layers(&mut self, raw_input: &RawInput) {
+        let mut changed = false;
+        for layer in self.layers.iter_mut() {
+            if layer.update(raw_input) {
+                info!(
+                    "layer `{}` {}",
+                    layer.name,
+                    if layer.active {
+                        "activated"
+                    } else {
+                        "deactivated"
+                    }
+                );
+                changed = true;
+            }
+        }
+        if changed {
+            info!("active layers: {:?}", self.active_layers());
+        }
+    }
+
+    // toggles must not stay latched while the player is not in control
+    fn update_mouse_lock(&mut self, raw_input: &RawInput) {
+        let mouse_lock = raw_input.mouse_lock();
+        if self.mouse_lock && !mouse_lock {
+            info!("mouse lock released, resetting toggles");
+            let profiles = self.profiles.values_mut();
+            let layers = self.layers.iter_mut().map(|layer| &mut layer.mappings);
+            for mappings in std::iter::once(&mut self.mappings)
+                .chain(profiles)
+                .chain(layers)
+            {
+                mappings.iter_mut().for_each(Mapping::reset);
+            }
+        }
+        self.mouse_lock = mouse_lock;
+    }
+
+    pub fn map_controller(&mut self, raw_input: &RawInput, controller: &mut dyn Controller) {
+        self.map_controller_at(raw_input, controller, Instant::now());
+    }
+
+    /// Applies the mappings as of `now`, time based mappings only look at this clock.
+    pub fn map_controller_at(
+        &mut self,
+        raw_input: &RawInput,
+        controller: &mut dyn Controller,
+        now: Instant,
+    ) {
+        self.update_mouse_lock(raw_input);
+        self.update_layers(raw_input);
+
+        let profile = match &self.active_profile {
+            Some(profile) => self.profiles.get_mut(profile).unwrap_or(&mut self.mappings),
+            None => &mut self.mappings,
+        };
+
+        // walk from the top layer down to the profile, collecting the outputs the layers above
+        // took over; hidden mappings drop their state so they do not resume latched or mid ramp
+        let mut hidden_outputs = Vec::new();
+        let mut visible = Vec::new();
+        let layers = self
+            .layers
+            .iter_mut()
+            .rev()
+            .filter(|layer| layer.active)
+            .map(|layer| &mut layer.mappings);
+        for mappings in layers.chain(std::iter::once(profile)) {
+            let mut outputs = Vec::new();
+            for mapping in mappings.iter_mut() {
+                let mapping_outputs = mapping.outputs();
+                let hidden = mapping_outputs
+                    .iter()
+                    .any(|output| hidden_outputs.contains(output));
+                outputs.extend(mapping_outputs);
+                if hidden {
+                    mapping.reset();
+                } else {
+                    visible.push(mapping);
+                }
+            }
+            hidden_outputs.extend(outputs);
+        }
+
+        let scale = Scale::of(&self.modifiers, raw_input);
+
+        // held exclusive chords keep the mappings on their single keys from firing
+        let chords = visible
+            .iter()
+            .filter_map(|mapping| mapping.exclusive_chord(raw_input))
+            .cloned()
+            .collect::<Vec<_>>();
+        let visible = visible
+            .into_iter()
+            .filter(|mapping| !chords.iter().any(|chord| mapping.is_part_of(chord)))
+            .collect::<Vec<_>>();
+        let claimed = visible
+            .iter()
+            .filter_map(|mapping| mapping.claimed_axis(raw_input))
+            .collect::<Vec<_>>();
+        for mapping in visible {
+            mapping.map_controller(raw_input, controller, now, scale, &claimed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{mock::MockController, DPad};
+
+    fn key(name: &str) -> Key {
+        name.parse().unwrap()
+    }
+
+    fn mapper(json: &str) -> Mapper {
+        json.parse().unwrap()
+    }
+
+    // a mapper with one mapping made of the shared `fields` and the `options` under test
+    fn single_mapping(fields: &str, options: &str) -> Mapper {
+        if options.is_empty() {
+            mapper(&format!("[{{ {} }}]", fields))
+        } else {
+            mapper(&format!("[{{ {}, {} }}]", fields, options))
+        }
+    }
+
+    // maps one frame per entry at its millisecond offset, `input` prepares the raw input
+    // and the fresh controller of the frame, `read` picks what the test looks at
+    fn run_frames<S, T>(
+        mapper: &mut Mapper,
+        frames: &[(u64, S)],
+        mut input: impl FnMut(&mut RawInput, &mut MockController, &S),
+        read: impl Fn(&MockController) -> T,
+    ) -> Vec<T> {
+        let start = Instant::now();
+        let mut raw_input = RawInput::mock();
+        frames
+            .iter()
+            .map(|(ms, state)| {
+                let mut controller = MockController::default();
+                input(&mut raw_input, &mut controller, state);
+                mapper.map_controller_at(
+                    &raw_input,
+                    &mut controller,
+                    start + Duration::from_millis(*ms),
+                );
+                read(&controller)
+            })
+            .collect()
+    }
+
+    // like `run_frames` with `key` held down or released in every frame
+    fn frames<T>(
+        mapper: &mut Mapper,
+        key: Key,
+        frames: &[(u64, bool)],
+        read: impl Fn(&MockController) -> T,
+    ) -> Vec<T> {
+        run_frames(
+            mapper,
+            frames,
+            |raw_input, _, down| raw_input.set_key(key, *down),
+            read,
+        )
+    }
+
+    #[test]
+    fn button_mapping() {
+        let mut mapper = mapper(r#"[{ "type": "Button", "input": "space", "output": "cross" }]"#);
+        let mut raw_input = RawInput::mock();
+        let mut controller = MockController::default();
+
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(!controller.btn(Button::Cross));
+
+        raw_input.set_key(key("space"), true);
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(controller.btn(Button::Cross));
+    }
+
+    #[test]
+    fn axis_mapping() {
+        let mut mapper = mapper(
+            r#"[
+                { "type": "Axis", "input": "a", "output": "lx", "value": -1 },
+                { "type": "Axis", "input": "mouse1", "output": "r2", "value": 1 }
+            ]"#,
+        );
+        let mut raw_input = RawInput::mock();
+        let mut controller = MockController::default();
+
+        raw_input.set_key(key("a"), true);
+        raw_input.set_key(key("mouse1"), true);
+        mapper.map_controller(&raw_input, &mut controller);
+        assert_eq!(controller.axis(Axis::Lx), 0);
+        assert_eq!(controller.axis(Axis::Ly), 128);
+        assert_eq!(controller.axis(Axis::R2), 255);
+    }
+
+    #[test]
+    fn dpad_diagonal() {
+        let mut mapper = mapper(
+            r#"[
+                { "type": "Button", "input": "up", "output": "dpad_up" },
+                { "type": "Button", "input": "right", "output": "dpad_right" }
+            ]"#,
+        );
+        let mut raw_input = RawInput::mock();
+        let mut controller = MockController::default();
+
+        raw_input.set_key(key("up"), true);
+        raw_input.set_key(key("right"), true);
+        mapper.map_controller(&raw_input, &mut controller);
+        assert_eq!(controller.dpad(), DPad::UpRight);
+    }
+
+    #[test]
+    fn gyro_mapping() {
+        let mut mapper = mapper(r#"[{ "type": "Gyro", "scale_x": 0.1, "scale_y": 0.1 }]"#);
+        let mut raw_input = RawInput::mock();
+        let mut controller = MockController::default();
+
+        raw_input.set_mouse(10, -10);
+        mapper.map_controller(&raw_input, &mut controller);
+        let gyro = controller.gyro();
+        assert!(gyro[0] > 0);
+        assert!(gyro[1] < 0);
+        assert_eq!(gyro[2], 0);
+    }
+
+    #[test]
+    fn touchpad_mapping() {
+        let mut mapper = mapper(
+            r#"[{
+                "type": "Touchpad", "input": "m", "finger": 1, "click": true,
+                "contact": "fixed", "position": [100, 200]
+            }]"#,
+        );
+        let mut raw_input = RawInput::mock();
+        let mut controller = MockController::default();
+
+        raw_input.set_key(key("m"), true);
+        mapper.map_controller(&raw_input, &mut controller);
+        let point = controller.touch_point(1);
+        assert!(point.active);
+        assert_eq!((point.x, point.y), (100, 200));
+        assert!(controller.btn(Button::Touch));
+
+        // touches take chords like the other mappings
+        let mut chord = self::mapper(
+            r#"[{
+                "type": "Touchpad", "input": "shift+m",
+                "contact": "fixed", "position": [100, 200]
+            }]"#,
+        );
+        let mut controller = MockController::default();
+        chord.map_controller(&raw_input, &mut controller);
+        assert!(!controller.touch_point(0).active);
+        raw_input.set_key(key("shift"), true);
+        chord.map_controller(&raw_input, &mut controller);
+        assert!(controller.touch_point(0).active);
+    }
+
+    #[test]
+    fn invalid_mappings() {
+        match r#"[{ "type": "Button", "input": "q", "output": "trinagle" }]"#.parse::<Mapper>() {
+            Err(LoadError::Mapping(mapping)) => {
+                assert_eq!(mapping.index, 1);
+                assert_eq!(mapping.context, None);
+                assert_eq!(mapping.entry["output"], "trinagle");
+                assert_eq!(mapping.unknown_name.as_deref(), Some("trinagle"));
+            }
+            _ => panic!("unknown output accepted"),
+        }
+        let json = r#"{ "profiles": { "driving": [
+            { "type": "Axis", "input": "w", "output": "r2", "value": 1 },
+            { "type": "Axis", "input": "s", "output": "l5", "value": 1 }
+        ] } }"#;
+        match json.parse::<Mapper>() {
+            Err(LoadError::Mapping(mapping)) => {
+                assert_eq!(mapping.index, 2);
+                assert_eq!(mapping.context.as_deref(), Some("profile `driving`"));
+                assert_eq!(mapping.unknown_name.as_deref(), Some("l5"));
+            }
+            _ => panic!("unknown output accepted"),
+        }
+        assert!(
+            r#"[{ "type": "Button", "input": "qq", "output": "triangle" }]"#
+                .parse::<Mapper>()
+                .is_err()
+        );
+        assert!(r#"[{
+                "type": "Touchpad", "input": "m", "finger": 2,
+                "contact": "fixed", "position": [100, 200]
+            }]"#
+        .parse::<Mapper>()
+        .is_err());
+    }
+
+    #[test]
+    fn profile_object() {
+        let mapper = mapper(
+            r#"{
+                "mappings": [{ "type": "Button", "input": "space", "output": "cross" }],
+                "virtual_controller": { "mac_address": "a4:15:66:01:02:03" }
+            }"#,
+        );
+        assert_eq!(mapper.mappings.len(), 1);
+        assert_eq!(
+            mapper.virtual_controller().unwrap().mac_address.to_string(),
+            "a4:15:66:01:02:03"
+        );
+
+        assert!(r#"{ "mapings": [] }"#.parse::<Mapper>().is_err());
+        assert!(
+            r#"{ "virtual_controller": { "firmware_date": "a very long date string" } }"#
+                .parse::<Mapper>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn feedback_section() {
+        let mapper = mapper(
+            r#"{
+                "mappings": [],
+                "feedback": { "min_intensity": 0.25, "sinks": [{ "type": "Log" }] }
+            }"#,
+        );
+        assert!(mapper.mappings.is_empty());
+
+        assert!(r#"{ "feedback": { "min_intensity": 2 } }"#.parse::<Mapper>().is_err());
+        assert!(r#"{ "feedback": { "sinks": [{ "type": "Telegraph" }] } }"#
+            .parse::<Mapper>()
+            .is_err());
+
+        // profiles are lists of mappings or objects that also override the feedback settings
+        let mapper = self::mapper(
+            r#"{
+                "feedback": { "min_intensity": 0.25 },
+                "profiles": {
+                    "menu": [],
+                    "driving": {
+                        "mappings": [{ "type": "Button", "input": "space", "output": "cross" }],
+                        "feedback": { "min_duration": 200 }
+                    }
+                }
+            }"#,
+        );
+        assert_eq!(mapper.profiles["driving"].len(), 1);
+        assert!(mapper.profile_feedback.contains_key("driving"));
+        assert!(!mapper.profile_feedback.contains_key("menu"));
+
+        assert!(
+            r#"{ "profiles": { "driving": { "feedback": { "min_intensity": -1 } } } }"#
+                .parse::<Mapper>()
+                .is_err()
+        );
+        assert!(
+            r#"{ "profiles": { "driving": { "feedback": { "min_intenisty": 1 } } } }"#
+                .parse::<Mapper>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn lightbar_profiles() {
+        let mut mapper = mapper(
+            r#"{
+                "mappings": [{ "type": "Button", "input": "space", "output": "cross" }],
+                "profiles": {
+                    "menu": [{ "type": "Button", "input": "space", "output": "circle" }]
+                },
+                "lightbar": [{ "min": [240, 240, 240], "max": [255, 255, 255], "profile": "menu" }]
+            }"#,
+        );
+        let mut raw_input = RawInput::mock();
+        raw_input.set_key(key("space"), true);
+        let now = Instant::now();
+
+        // the lightbar starts out black, but nothing has been sent yet
+        mapper.update_output(&OutputState::default(), now);
+        assert_eq!(mapper.active_profile, None);
+
+        let mut output_state = OutputState {
+            lightbar: [255, 250, 245],
+            updated_at: Some(now),
+            ..OutputState::default()
+        };
+        mapper.update_output(&output_state, now);
+        assert_eq!(mapper.active_profile.as_deref(), Some("menu"));
+
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(controller.btn(Button::Circle));
+        assert!(!controller.btn(Button::Cross));
+
+        output_state.lightbar = [0, 0, 255];
+        mapper.update_output(&output_state, now);
+        assert_eq!(mapper.active_profile, None);
+
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(controller.btn(Button::Cross));
+    }
+
+    #[test]
+    fn invalid_lightbar_rules() {
+        assert!(
+            r#"{ "lightbar": [{ "min": [0, 0, 0], "max": [9, 9, 9], "profile": "menu" }] }"#
+                .parse::<Mapper>()
+                .is_err()
+        );
+        assert!(r#"{
+                "profiles": { "menu": [] },
+                "lightbar": [{ "min": [9, 0, 0], "max": [0, 9, 9], "profile": "menu" }]
+            }"#
+        .parse::<Mapper>()
+        .is_err());
+    }
+
+    #[test]
+    fn layers() {
+        let mut mapper = mapper(
+            r#"{
+                "mappings": [
+                    { "type": "Axis", "input": "w", "output": "ly", "value": -1 },
+                    { "type": "Button", "input": "space", "output": "cross" },
+                    { "type": "Button", "input": "e", "output": "triangle" }
+                ],
+                "layers": [
+                    {
+                        "name": "vehicle", "key": "v", "activation": "toggle",
+                        "mappings": [
+                            { "type": "Axis", "input": "w", "output": "r2", "value": 1 },
+                            { "type": "Button", "input": "q", "output": "triangle" }
+                        ]
+                    },
+                    {
+                        "name": "emotes", "key": "alt",
+                        "mappings": [{ "type": "Button", "input": "space", "output": "square" }]
+                    }
+                ]
+            }"#,
+        );
+        let mut raw_input = RawInput::mock();
+        raw_input.set_key(key("w"), true);
+        raw_input.set_key(key("space"), true);
+        raw_input.set_key(key("e"), true);
+
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(mapper.active_layers().is_empty());
+        assert_eq!(controller.axis(Axis::Ly), 0);
+        assert!(controller.btn(Button::Triangle));
+
+        // toggled on, w drives r2 as well and q takes over triangle from e
+        raw_input.set_key(key("v"), true);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert_eq!(mapper.active_layers(), vec!["vehicle"]);
+        assert_eq!(controller.axis(Axis::Ly), 0);
+        assert_eq!(controller.axis(Axis::R2), 255);
+        assert!(!controller.btn(Button::Triangle));
+        assert!(controller.btn(Button::Cross));
+
+        // held on top of it
+        raw_input.set_key(key("v"), false);
+        raw_input.set_key(key("alt"), true);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert_eq!(mapper.active_layers(), vec!["vehicle", "emotes"]);
+        assert!(controller.btn(Button::Square));
+        assert!(controller.btn(Button::Cross));
+        assert!(!controller.btn(Button::Triangle));
+
+        // released and toggled off again
+        raw_input.set_key(key("alt"), false);
+        raw_input.set_key(key("v"), true);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(mapper.active_layers().is_empty());
+        assert_eq!(controller.axis(Axis::Ly), 0);
+        assert!(controller.btn(Button::Triangle));
+    }
+
+    #[test]
+    fn layers_hide_by_output() {
+        let mut mapper = mapper(
+            r#"{
+                "mappings": [
+                    { "type": "KeyStick", "up": "w", "down": "s", "left": "a", "right": "d",
+                      "output_x": "lx", "output_y": "ly" },
+                    { "type": "Button", "input": "c", "output": "circle", "mode": "toggle" }
+                ],
+                "layers": [{
+                    "name": "boost", "key": "shift",
+                    "mappings": [
+                        { "type": "Button", "input": "w", "output": "r1" },
+                        { "type": "Button", "input": "x", "output": "circle" }
+                    ]
+                }]
+            }"#,
+        );
+        let mut raw_input = RawInput::mock();
+        let mut frame = |raw_input: &RawInput| {
+            let mut controller = MockController::default();
+            mapper.map_controller(raw_input, &mut controller);
+            (
+                controller.axis(Axis::Ly),
+                controller.btn(Button::R1),
+                controller.btn(Button::Circle),
+            )
+        };
+
+        raw_input.set_key(key("c"), true);
+        assert_eq!(frame(&raw_input), (128, false, true));
+        raw_input.set_key(key("c"), false);
+        assert_eq!(frame(&raw_input), (128, false, true));
+
+        // binding w in the layer leaves the stick alone, only the toggle on circle is hidden
+        raw_input.set_key(key("shift"), true);
+        raw_input.set_key(key("w"), true);
+        assert_eq!(frame(&raw_input), (0, true, false));
+
+        // and comes back unlatched once the layer is released
+        raw_input.set_key(key("shift"), false);
+        assert_eq!(frame(&raw_input), (0, false, false));
+    }
+
+    #[test]
+    fn chords() {
+        let mut mapper = mapper(
+            r#"[
+                { "type": "Button", "input": "e", "output": "triangle" },
+                { "type": "Button", "input": "shift", "output": "l3" },
+                { "type": "Button", "input": "shift+e", "output": "circle", "exclusive": true },
+                { "type": "Axis", "input": ["ctrl", "q"], "output": "l2", "value": 1 }
+            ]"#,
+        );
+        let mut raw_input = RawInput::mock();
+
+        raw_input.set_key(key("e"), true);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(controller.btn(Button::Triangle));
+        assert!(!controller.btn(Button::Circle));
+
+        // the exclusive chord keeps e and shift from firing on their own
+        raw_input.set_key(key("shift"), true);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(controller.btn(Button::Circle));
+        assert!(!controller.btn(Button::Triangle));
+        assert!(!controller.btn(Button::L3));
+
+        raw_input.set_key(key("q"), true);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert_eq!(controller.axis(Axis::L2), 0);
+
+        raw_input.set_key(key("ctrl"), true);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert_eq!(controller.axis(Axis::L2), 255);
+    }
+
+    #[test]
+    fn toggle_modes() {
+        let mut mapper = mapper(
+            r#"[
+                { "type": "Button", "input": "c", "output": "circle", "mode": "toggle" },
+                { "type": "Button", "input": "shift", "output": "l3", "mode": "toggle_on_release" },
+                { "type": "Axis", "input": "mouse2", "output": "l2", "value": 1, "mode": "toggle" }
+            ]"#,
+        );
+        let mut raw_input = RawInput::mock();
+        let mut frame = |raw_input: &RawInput| {
+            let mut controller = MockController::default();
+            mapper.map_controller(raw_input, &mut controller);
+            (
+                controller.btn(Button::Circle),
+                controller.btn(Button::L3),
+                controller.axis(Axis::L2),
+            )
+        };
+
+        raw_input.set_key(key("c"), true);
+        raw_input.set_key(key("shift"), true);
+        raw_input.set_key(key("mouse2"), true);
+        assert_eq!(frame(&raw_input), (true, false, 255));
+
+        raw_input.set_key(key("c"), false);
+        raw_input.set_key(key("shift"), false);
+        raw_input.set_key(key("mouse2"), false);
+        assert_eq!(frame(&raw_input), (true, true, 255));
+
+        raw_input.set_key(key("c"), true);
+        assert_eq!(frame(&raw_input), (false, true, 255));
+
+        // losing the mouse lock releases everything that is latched
+        raw_input.set_key(key("c"), false);
+        raw_input.set_mouse_lock(false);
+        assert_eq!(frame(&raw_input), (false, false, 0));
+    }
+
+    #[test]
+    fn turbo() {
+        let mut mapper = mapper(
+            r#"[{
+                "type": "Button", "input": "mouse1", "output": "r2",
+                "turbo": { "rate": 10, "duty_cycle": 0.25 }
+            }]"#,
+        );
+
+        // 100ms periods, pressed for the first 25ms of each
+        let held = [0, 10, 20, 30, 90, 100, 124, 125, 210]
+            .iter()
+            .map(|ms| (*ms, true))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frames(&mut mapper, key("mouse1"), &held, |c| c.btn(Button::R2)),
+            vec![true, true, true, false, false, true, true, false, true]
+        );
+
+        assert!(r#"[{
+                "type": "Button", "input": "mouse1", "output": "r2",
+                "turbo": { "rate": 10, "duty_cycle": 1 }
+            }]"#
+        .parse::<Mapper>()
+        .is_err());
+    }
+
+    fn macro_frames(mapper: &mut Mapper, presses: &[(u64, bool)]) -> Vec<(bool, u8)> {
+        frames(mapper, key("g"), presses, |c| {
+            (c.btn(Button::R1), c.axis(Axis::Lx))
+        })
+    }
+
+    #[test]
+    fn macro_sequence() {
+        let mut mapper = mapper(
+            r#"[{
+                "type": "Macro", "input": "g",
+                "steps": [
+                    { "press": "r1" }, { "wait": 50 }, { "release": "r1" },
+                    { "axis": { "output": "lx", "value": -1 } }, { "wait": 50 }
+                ]
+            }]"#,
+        );
+        assert_eq!(
+            macro_frames(
+                &mut mapper,
+                &[
+                    (0, true),
+                    (20, false),
+                    (50, false),
+                    (80, false),
+                    (100, false),
+                    (120, false)
+                ]
+            ),
+            vec![
+                (true, 128),
+                (true, 128),
+                (false, 0),
+                (false, 0),
+                (false, 0),
+                (false, 128)
+            ]
+        );
+    }
+
+    fn macro_mapper(options: &str) -> Mapper {
+        single_mapping(
+            r#""type": "Macro", "input": "g", "steps": [{ "press": "r1" }, { "wait": 50 }]"#,
+            options,
+        )
+    }
+
+    #[test]
+    fn macro_options() {
+        // cancelled as soon as the key goes up
+        let mut mapper = macro_mapper(r#""cancel_on_release": true"#);
+        assert_eq!(
+            macro_frames(&mut mapper, &[(0, true), (20, false), (30, false)]),
+            vec![(true, 128), (false, 128), (false, 128)]
+        );
+
+        // repeated while held
+        let mut mapper = macro_mapper(r#""repeat": true"#);
+        let presses = [
+            (0, true),
+            (50, true),
+            (60, true),
+            (120, false),
+            (130, false),
+        ];
+        assert_eq!(
+            macro_frames(&mut mapper, &presses),
+            vec![
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (false, 128)
+            ]
+        );
+
+        // a second press is queued
+        let mut mapper = macro_mapper("");
+        let presses = [
+            (0, true),
+            (10, false),
+            (20, true),
+            (50, false),
+            (60, false),
+            (110, false),
+            (120, false),
+        ];
+        assert_eq!(
+            macro_frames(&mut mapper, &presses),
+            vec![
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (false, 128)
+            ]
+        );
+
+        // or restarts the macro
+        let mut mapper = macro_mapper(r#""retrigger": "restart""#);
+        let presses = [
+            (0, true),
+            (10, false),
+            (40, true),
+            (60, false),
+            (90, false),
+            (100, false),
+        ];
+        assert_eq!(
+            macro_frames(&mut mapper, &presses),
+            vec![
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (true, 128),
+                (false, 128)
+            ]
+        );
+    }
+
+    #[test]
+    fn macro_release_keeps_other_mappings() {
+        let mut mapper = mapper(
+            r#"[
+                { "type": "Button", "input": "space", "output": "r1" },
+                { "type": "Macro", "input": "g", "steps": [{ "release": "r1" }, { "wait": 50 }] }
+            ]"#,
+        );
+        let mut raw_input = RawInput::mock();
+        raw_input.set_key(key("space"), true);
+        raw_input.set_key(key("g"), true);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        assert!(controller.btn(Button::R1));
+    }
+
+    fn tap_hold_frames(mapper: &mut Mapper, presses: &[(u64, bool)]) -> Vec<[bool; 3]> {
+        frames(mapper, key("e"), presses, |c| {
+            [
+                c.btn(Button::R1),
+                c.btn(Button::Triangle),
+                c.btn(Button::Square),
+            ]
+        })
+    }
+
+    #[test]
+    fn tap_hold() {
+        let json = r#"[{
+            "type": "TapHold", "input": "e",
+            "tap": "r1", "hold": "triangle", "double_tap": "square",
+            "hold_time": 200, "double_tap_time": 250, "tap_duration": 50
+        }]"#;
+        let none = [false, false, false];
+        let tap = [true, false, false];
+        let hold = [false, true, false];
+        let double_tap = [false, false, true];
+
+        let mut tapped = mapper(json);
+        let mut held = mapper(json);
+        let mut double_tapped = mapper(json);
+
+        // a tap is reported once no second tap can follow and lasts tap_duration
+        let presses = [
+            (0, true),
+            (100, false),
+            (300, false),
+            (350, false),
+            (400, false),
+        ];
+        assert_eq!(
+            tap_hold_frames(&mut tapped, &presses),
+            vec![none, none, none, tap, none]
+        );
+
+        let presses = [
+            (0, true),
+            (150, true),
+            (200, true),
+            (500, true),
+            (510, false),
+        ];
+        assert_eq!(
+            tap_hold_frames(&mut held, &presses),
+            vec![none, none, hold, hold, none]
+        );
+
+        let presses = [
+            (0, true),
+            (100, false),
+            (200, true),
+            (400, true),
+            (410, false),
+            (500, false),
+        ];
+        assert_eq!(
+            tap_hold_frames(&mut double_tapped, &presses),
+            vec![none, none, double_tap, double_tap, none, none]
+        );
+    }
+
+    #[test]
+    fn tap_hold_needs_alternative() {
+        assert!(r#"[{ "type": "TapHold", "input": "e", "tap": "r1" }]"#
+            .parse::<Mapper>()
+            .is_err());
+    }
+
+    #[test]
+    fn key_stick() {
+        let fields = r#""type": "KeyStick", "up": "w", "down": "s", "left": "a", "right": "d",
+            "output_x": "lx", "output_y": "ly""#;
+        let mut last_wins = single_mapping(fields, r#""socd": "last_wins""#);
+        let mut neutral = single_mapping(fields, r#""socd": "neutral""#);
+        let mut first_wins = single_mapping(fields, r#""socd": "first_wins""#);
+        let mut raw_input = RawInput::mock();
+
+        let stick = |mapper: &mut Mapper, raw_input: &RawInput| {
+            let mut controller = MockController::default();
+            mapper.map_controller(raw_input, &mut controller);
+            (controller.axis(Axis::Lx), controller.axis(Axis::Ly))
+        };
+
+        // diagonals stay on the unit circle
+        raw_input.set_key(key("w"), true);
+        raw_input.set_key(key("d"), true);
+        assert_eq!(stick(&mut last_wins, &raw_input), (218, 37));
+        raw_input.set_key(key("w"), false);
+        assert_eq!(stick(&mut last_wins, &raw_input), (255, 128));
+        raw_input.set_key(key("d"), false);
+
+        raw_input.set_key(key("a"), true);
+        for mapper in [&mut last_wins, &mut neutral, &mut first_wins] {
+            assert_eq!(stick(mapper, &raw_input), (0, 128));
+        }
+        raw_input.set_key(key("d"), true);
+        assert_eq!(stick(&mut last_wins, &raw_input), (255, 128));
+        assert_eq!(stick(&mut neutral, &raw_input), (128, 128));
+        assert_eq!(stick(&mut first_wins, &raw_input), (0, 128));
+    }
+
+    #[test]
+    fn modifiers() {
+        let json = r#"{
+            "mappings": [
+                { "type": "Axis", "input": "a", "output": "lx", "value": -1 },
+                { "type": "Axis", "input": "mouse1", "output": "r2", "value": 1 },
+                {
+                    "type": "Mouse", "output_x": "rx", "output_y": "ry",
+                    "multiplier_x": 1, "multiplier_y": 1, "dead_zone_x": 0, "dead_zone_y": 0,
+                    "sensitivity": 1, "exponent": 1, "shape": "circle"
+                }
+            ],
+            "modifiers": [
+                { "key": "alt", "stick": 0.5 },
+                { "key": "ctrl", "stick": 0.5, "mouse": 0.5 }
+            ]
+        }"#;
+        let frame = |modifiers: &[&str]| {
+            let mut mapper = mapper(json);
+            let mut raw_input = RawInput::mock();
+            raw_input.set_key(key("a"), true);
+            raw_input.set_key(key("mouse1"), true);
+            raw_input.set_mouse(10, 0);
+            for modifier in modifiers {
+                raw_input.set_key(key(modifier), true);
+            }
+            let mut controller = MockController::default();
+            mapper.map_controller(&raw_input, &mut controller);
+            (
+                controller.axis(Axis::Lx),
+                controller.axis(Axis::R2),
+                controller.axis(Axis::Rx),
+            )
+        };
+
+        let (lx, r2, rx) = frame(&[]);
+        assert_eq!((lx, r2), (0, 255));
+
+        // triggers are left alone
+        let (walk_lx, walk_r2, walk_rx) = frame(&["alt"]);
+        assert_eq!((walk_lx, walk_r2, walk_rx), (63, 255, rx));
+
+        // held modifiers multiply
+        let (both_lx, _, both_rx) = frame(&["alt", "ctrl"]);
+        assert_eq!(both_lx, 95);
+        assert!(both_rx > 128 && both_rx < rx);
+    }
+
+    #[test]
+    fn invalid_modifier() {
+        assert!(r#"{ "modifiers": [{ "key": "alt", "stick": 2 }] }"#
+            .parse::<Mapper>()
+            .is_err());
+        assert!(r#"{ "modifiers": [{ "key": "alt", "mouse": 0 }] }"#
+            .parse::<Mapper>()
+            .is_err());
+    }
+
+    #[test]
+    fn axis_ramps() {
+        // the real stick reports 42, mappings that let go leave it there
+        let ramp = |json: &str, axis: Axis, presses: &[(u64, bool)]| {
+            run_frames(
+                &mut mapper(json),
+                presses,
+                |raw_input, controller, down| {
+                    raw_input.set_key(key("w"), *down);
+                    controller.set_axis(axis, 42);
+                },
+                |c| c.axis(axis),
+            )
+        };
+
+        // the trigger ramps up and back down to zero, then leaves the axis alone
+        let trigger = r#"[{
+            "type": "Axis", "input": "w", "output": "r2", "value": 1,
+            "attack": { "time": 100 }, "release": { "time": 200 }
+        }]"#;
+        assert_eq!(
+            ramp(
+                trigger,
+                Axis::R2,
+                &[
+                    (0, true),
+                    (50, true),
+                    (100, true),
+                    (150, false),
+                    (250, false),
+                    (300, false)
+                ]
+            ),
+            vec![0, 127, 255, 191, 63, 42]
+        );
+
+        // without a release ramp the axis is let go right away
+        let stick = r#"[{
+            "type": "Axis", "input": "w", "output": "ly", "value": -1,
+            "attack": { "time": 100, "curve": "exponential" }, "release": { "time": 0 }
+        }]"#;
+        assert_eq!(
+            ramp(
+                stick,
+                Axis::Ly,
+                &[(0, true), (100, true), (1000, true), (1010, false)]
+            ),
+            vec![128, 47, 0, 42]
+        );
+    }
+
+    #[test]
+    fn release_ramp_yields() {
+        let mut mapper = mapper(
+            r#"[
+                { "type": "Axis", "input": "d", "output": "lx", "value": 1 },
+                {
+                    "type": "Axis", "input": "a", "output": "lx", "value": -1,
+                    "release": { "time": 200 }
+                }
+            ]"#,
+        );
+        let presses = [
+            (0, (true, false)),
+            (10, (false, true)),
+            (20, (false, false)),
+        ];
+        let lx = run_frames(
+            &mut mapper,
+            &presses,
+            |raw_input, _, (a, d)| {
+                raw_input.set_key(key("a"), *a);
+                raw_input.set_key(key("d"), *d);
+            },
+            |c| c.axis(Axis::Lx),
+        );
+
+        // the release ramp of a stops once d takes over the stick and does not come back
+        assert_eq!(lx, vec![0, 255, 128]);
+    }
+
+    #[test]
+    fn mouse_gesture() {
+        let mut mapper = mapper(
+            r#"[{
+                "type": "MouseGesture", "direction": "left", "threshold": 5000,
+                "output": "circle", "duration": 20, "cooldown": 100
+            }]"#,
+        );
+        let flicks = [
+            (0, -10),
+            (4, 40),
+            (8, -20),
+            (12, 0),
+            (28, 0),
+            (32, -40),
+            (104, 0),
+            (108, -40),
+        ];
+        let pressed = run_frames(
+            &mut mapper,
+            &flicks,
+            |raw_input, _, mouse_x| raw_input.set_mouse(*mouse_x, 0),
+            |c| c.btn(Button::Circle),
+        );
+
+        // 5000 counts per second are 20 counts per 4ms report, then it cools down for 100ms
+        assert_eq!(
+            pressed,
+            vec![false, false, true, true, false, false, false, true]
+        );
+    }
+
+    // moves the mouse once, returns the stick deflection from the center and the residue
+    fn dead_zone_frame(shape: &str, mouse: [i32; 2]) -> ([i32; 2], [f64; 2]) {
+        let mut mapper = single_mapping(
+            r#""type": "Mouse", "output_x": "rx", "output_y": "ry",
+                "multiplier_x": 1, "multiplier_y": 1, "dead_zone_x": 20, "dead_zone_y": 10,
+                "sensitivity": 1, "exponent": 1"#,
+            &format!(r#""shape": "{}""#, shape),
+        );
+        let mut raw_input = RawInput::mock();
+        raw_input.set_mouse(mouse[0], mouse[1]);
+        let mut controller = MockController::default();
+        mapper.map_controller(&raw_input, &mut controller);
+        let residue = match &mapper.mappings[0] {
+            Mapping::Mouse(mapping) => mapping.residue,
+            _ => unreachable!(),
+        };
+        (
+            [
+                controller.axis(Axis::Rx) as i32 - 128,
+                controller.axis(Axis::Ry) as i32 - 128,
+            ],
+            residue,
+        )
+    }
+
+    #[test]
+    fn dead_zone_shapes() {
+        // the output leaves the dead zone of the shape, truncation may cost one step
+        type Outside = fn([f64; 2], [i32; 2]) -> bool;
+        let outside: [(&str, Outside); 4] = [
+            ("circle", |d, _| {
+                f64::hypot(d[0] / 20f64, d[1] / 10f64) >= 1f64
+            }),
+            ("square", |d, _| {
+                d[0] / 20f64 >= 1f64 || d[1] / 10f64 >= 1f64
+            }),
+            ("axial", |d, mouse| {
+                (mouse[0] == 0 || d[0] >= 20f64) && (mouse[1] == 0 || d[1] >= 10f64)
+            }),
+            ("cross", |d, mouse| {
+                if mouse[0].abs() >= mouse[1].abs() {
+                    d[0] >= 20f64
+                } else {
+                    d[1] >= 10f64
+                }
+            }),
+        ];
+
+        for (shape, outside) in outside.iter() {
+            for x in -4..=4 {
+                for y in -4..=4 {
+                    if x == 0 && y == 0 {
+                        continue;
+                    }
+                    let (deflection, residue) = dead_zone_frame(shape, [x, y]);
+                    let message =
+                        format!("{} {:?} -> {:?} {:?}", shape, [x, y], deflection, residue);
+
+                    assert_eq!(deflection[0].signum(), x.signum(), "{}", message);
+                    assert_eq!(deflection[1].signum(), y.signum(), "{}", message);
+                    let distance = [
+                        (deflection[0].abs() + 1) as f64,
+                        (deflection[1].abs() + 1) as f64,
+                    ];
+                    assert!(outside(distance, [x, y]), "{}", message);
+                    assert!(residue.iter().all(|r| r.abs() < 1f64), "{}", message);
+                }
+            }
+        }
+
+        assert_eq!(dead_zone_frame("circle", [1, 1]).0, [16, 9]);
+        assert_eq!(dead_zone_frame("square", [1, 1]).0, [12, 12]);
+        assert_eq!(dead_zone_frame("axial", [1, 1]).0, [22, 12]);
+        assert_eq!(dead_zone_frame("cross", [1, 1]).0, [22, 2]);
+    }
+
+    #[test]
+    fn unknown_dead_zone_shape() {
+        assert!(r#"[{
+            "type": "Mouse", "output_x": "rx", "output_y": "ry",
+            "multiplier_x": 1, "multiplier_y": 1, "dead_zone_x": 0, "dead_zone_y": 0,
+            "sensitivity": 1, "exponent": 1, "shape": "round"
+        }]"#
+        .parse::<Mapper>()
+        .is_err());
     }
 }